@@ -0,0 +1,96 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use physics::{BodyType, PhysicsWorld, Quad};
+
+// Times stepping a pile of boxes and counts the allocations made while doing it, so changes to the solve loop can be
+// compared with `cargo bench --bench solve` before and after. Criterion isnt a dependency, so this is timed by hand
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+const TS: f32 = 0.01;
+const STEPS: usize = 100;
+const SAMPLES: usize = 5;
+
+// A floor with a grid of boxes just above it, the same every run so the timings can be compared
+fn pile(columns: usize, rows: usize) -> PhysicsWorld {
+    let mut world = PhysicsWorld::default();
+    world.add_quad(Quad {
+        position: cgmath::vec2(0.0, -0.5),
+        scale: cgmath::vec2(columns as f32 * 2.0, 1.0),
+        body_type: BodyType::Static,
+        ..Quad::default()
+    });
+    for row in 0..rows {
+        for column in 0..columns {
+            world.add_quad(Quad {
+                position: cgmath::vec2(
+                    (column as f32 - (columns - 1) as f32 * 0.5) * 1.1,
+                    0.55 + row as f32 * 1.1,
+                ),
+                ..Quad::default()
+            });
+        }
+    }
+    world
+}
+
+fn bench(name: &str, columns: usize, rows: usize) {
+    let mut times = Vec::with_capacity(SAMPLES);
+    let mut allocations = 0;
+    let mut capped_steps = 0;
+    for _ in 0..SAMPLES {
+        let mut world = pile(columns, rows);
+        // Let the pile land first, so most of the timed steps are the solver working on resting contacts
+        world.step_n(TS, STEPS);
+
+        let allocations_before = ALLOCATIONS.load(Ordering::Relaxed);
+        let start = Instant::now();
+        for _ in 0..STEPS {
+            world.step(TS);
+            capped_steps += world.last_step_report().hit_iteration_cap as usize;
+        }
+        times.push(start.elapsed());
+        allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations_before;
+    }
+    times.sort();
+
+    let per_step = |time: Duration| time / STEPS as u32;
+    println!(
+        "{name}: {} quads, {:?} per step (min {:?}), {} allocations per step",
+        columns * rows + 1,
+        per_step(times[SAMPLES / 2]),
+        per_step(times[0]),
+        allocations / STEPS,
+    );
+    if capped_steps > 0 {
+        println!("  {capped_steps} steps reached the iteration limit");
+    }
+}
+
+fn main() {
+    bench("small pile", 5, 5);
+    bench("large pile", 20, 10);
+}
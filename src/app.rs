@@ -1,7 +1,4 @@
-use std::sync::{
-    atomic::{AtomicBool, Ordering},
-    Arc,
-};
+use std::sync::Arc;
 
 use cgmath::prelude::*;
 use eframe::egui;
@@ -9,19 +6,259 @@ use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    get_collision, CameraUniform, Collider, Quad, Renderer, StorageBufferQuad, SweepingCollider,
-    MAX_PHYSICS_ITERATIONS,
+    load_png, raycast, save_png, Aabb, BackgroundUniform, BodyType, Camera, CameraUniform, Capsule,
+    Collider, ContactResolution, GravityAxes, GravityIntegration, GridUniform, Joint, PhysicsWorld,
+    Quad, Renderer, Scene, SpringJoint, StorageBufferQuad, SweepingMode, TextureRect,
+    CAMERA_SHAKE_AMOUNT, CAMERA_SHAKE_DURATION, DEBUG_NORMAL_SCALE, FLASH_DURATION,
+    MAX_FIXED_UPDATES_PER_FRAME, MAX_UNDO_HISTORY, SCENE_VERSION, SLINGSHOT_STRENGTH,
+    STUCK_STEPS_WARNING,
 };
 
-#[derive(Serialize, Deserialize)]
-pub struct Camera {
-    position: cgmath::Vector2<f32>,
-    rotation: f32,
-    zoom: f32,
+// The quads being dragged around with the mouse, the rest of the selection moves with the one under the cursor
+struct GrabbedQuad {
+    index: usize,
+    // From the cursor to the quad's position, so the quad doesnt jump to be centered on the cursor
+    offset: cgmath::Vector2<f32>,
+    // What each grabbed quad was before it was made kinematic so the solver doesnt move it
+    body_types: Vec<(usize, BodyType)>,
+    // How fast the cursor was moving it, given to the quad when it is let go
+    velocity: cgmath::Vector2<f32>,
+}
+
+// What an undo goes back to, the joints and springs are kept because deleting a quad removes or renumbers them
+#[derive(Clone, PartialEq)]
+struct EditSnapshot {
+    quads: Vec<Quad>,
+    joints: Vec<Joint>,
+    springs: Vec<SpringJoint>,
+}
+
+// Every action that can be run from the command palette, add new actions here so they show up in it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    AddQuad,
+    AddCapsule,
+    SeparateOverlaps,
+    ResetQuads,
+    ResetToCheckpoint,
+    ResetCamera,
+    TogglePhysics,
+    ToggleLockstep,
+    Step,
+    ToggleCameraLock,
+    ToggleSpawnMode,
+    ToggleContactCounts,
+    ToggleInfoWindow,
+    ToggleSettingsWindow,
+    ToggleQuadsWindow,
+    FrameAll,
+    FrameSelected,
+    DuplicateSelected,
+    Undo,
+    Redo,
+}
+
+impl Command {
+    pub const ALL: [Command; 20] = [
+        Self::AddQuad,
+        Self::AddCapsule,
+        Self::SeparateOverlaps,
+        Self::ResetQuads,
+        Self::ResetToCheckpoint,
+        Self::ResetCamera,
+        Self::TogglePhysics,
+        Self::ToggleLockstep,
+        Self::Step,
+        Self::ToggleCameraLock,
+        Self::ToggleSpawnMode,
+        Self::ToggleContactCounts,
+        Self::ToggleInfoWindow,
+        Self::ToggleSettingsWindow,
+        Self::ToggleQuadsWindow,
+        Self::FrameAll,
+        Self::FrameSelected,
+        Self::DuplicateSelected,
+        Self::Undo,
+        Self::Redo,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::AddQuad => "Add Quad",
+            Self::AddCapsule => "Add Capsule",
+            Self::SeparateOverlaps => "Separate Overlaps",
+            Self::ResetQuads => "Reset Quads to Initial State",
+            Self::ResetToCheckpoint => "Reset to Checkpoint",
+            Self::ResetCamera => "Reset Camera",
+            Self::TogglePhysics => "Toggle Physics",
+            Self::ToggleLockstep => "Toggle Lockstep",
+            Self::Step => "Step",
+            Self::ToggleCameraLock => "Toggle Camera Lock",
+            Self::ToggleSpawnMode => "Toggle Spawn Mode",
+            Self::ToggleContactCounts => "Toggle Contact Counts",
+            Self::ToggleInfoWindow => "Toggle Info Window",
+            Self::ToggleSettingsWindow => "Toggle Settings Window",
+            Self::ToggleQuadsWindow => "Toggle Quads Window",
+            Self::FrameAll => "Frame All",
+            Self::FrameSelected => "Frame Selected",
+            Self::DuplicateSelected => "Duplicate Selected",
+            Self::Undo => "Undo",
+            Self::Redo => "Redo",
+        }
+    }
+}
+
+// What quads are tinted by when drawing them, none of these change the color stored on the quad
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+enum ColorBy {
+    None,
+    Speed,
+    Sleeping,
+    BodyType,
+}
+
+impl ColorBy {
+    const ALL: [ColorBy; 4] = [Self::None, Self::Speed, Self::Sleeping, Self::BodyType];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::None => "None",
+            Self::Speed => "Speed",
+            Self::Sleeping => "Sleeping",
+            Self::BodyType => "Body Type",
+        }
+    }
+
+    // The labels and colors to show what each color means
+    fn legend(self, max_speed: f32) -> Vec<(String, cgmath::Vector3<f32>)> {
+        match self {
+            Self::None => vec![],
+            Self::Speed => vec![
+                ("0".into(), speed_color(0.0)),
+                (format!("{:.1}", max_speed * 0.5), speed_color(0.5)),
+                (format!("{max_speed:.1} or faster"), speed_color(1.0)),
+            ],
+            Self::Sleeping => vec![("Asleep".into(), SLEEPING_COLOR)],
+            Self::BodyType => BodyType::ALL
+                .into_iter()
+                .map(|body_type| (body_type.name().into(), body_type_color(body_type)))
+                .collect(),
+        }
+    }
+
+    // The color to draw the quad with instead of its own, if it has one
+    fn color(self, quad: &Quad, max_speed: f32) -> Option<cgmath::Vector3<f32>> {
+        match self {
+            Self::None => None,
+            Self::Speed => Some(speed_color(quad.velocity.magnitude() / max_speed)),
+            Self::Sleeping => quad.is_sleeping.then_some(SLEEPING_COLOR),
+            Self::BodyType => Some(body_type_color(quad.body_type)),
+        }
+    }
+}
+
+const SLEEPING_COLOR: cgmath::Vector3<f32> = cgmath::vec3(0.5, 0.5, 0.5);
+
+// Goes from blue through green to red as `t` goes from 0 to 1
+fn speed_color(t: f32) -> cgmath::Vector3<f32> {
+    let t = t.clamp(0.0, 1.0);
+    let (blue, green, red) = (
+        cgmath::vec3(0.1, 0.3, 1.0),
+        cgmath::vec3(0.1, 0.9, 0.2),
+        cgmath::vec3(1.0, 0.1, 0.1),
+    );
+    if t < 0.5 {
+        blue.lerp(green, t * 2.0)
+    } else {
+        green.lerp(red, t * 2.0 - 1.0)
+    }
+}
+
+fn body_type_color(body_type: BodyType) -> cgmath::Vector3<f32> {
+    match body_type {
+        BodyType::Dynamic => cgmath::vec3(0.2, 0.5, 1.0),
+        BodyType::Static => cgmath::vec3(0.4, 0.4, 0.4),
+        BodyType::Kinematic => cgmath::vec3(1.0, 0.6, 0.1),
+    }
+}
+
+// Whether all the characters of the query appear in the text in order, ignoring case and spaces
+fn fuzzy_match(query: &str, text: &str) -> bool {
+    let mut text = text.chars().flat_map(char::to_lowercase);
+    query
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .all(|q| text.any(|c| c == q))
+}
+
+// A copy that isnt exactly on top of the original
+fn duplicate(quad: &Quad, offset: cgmath::Vector2<f32>) -> Quad {
+    let mut copy = *quad;
+    copy.position += offset;
+    copy.initial = None;
+    copy
 }
 
+// Copies of the quad in a grid going right and up from it, not including the quad itself
+fn duplicate_grid(
+    quad: &Quad,
+    columns: usize,
+    rows: usize,
+    spacing: cgmath::Vector2<f32>,
+) -> impl Iterator<Item = Quad> + '_ {
+    (0..rows)
+        .flat_map(move |row| (0..columns).map(move |column| (column, row)))
+        .skip(1)
+        .map(move |(column, row)| {
+            duplicate(
+                quad,
+                cgmath::vec2(column as f32 * spacing.x, row as f32 * spacing.y),
+            )
+        })
+}
+
+// A thin quad stretched between two points
+fn rod(
+    a: cgmath::Vector2<f32>,
+    b: cgmath::Vector2<f32>,
+    color: cgmath::Vector3<f32>,
+) -> StorageBufferQuad {
+    let difference = b - a;
+    StorageBufferQuad {
+        position: (a + b) * 0.5,
+        scale: cgmath::vec2(difference.magnitude(), 0.05),
+        color: color.extend(1.0),
+        // Positive rotation is clockwise
+        rotation: -difference.y.atan2(difference.x),
+        uv_offset: cgmath::vec2(0.0, 0.0),
+        uv_size: cgmath::vec2(0.0, 0.0),
+        textured: 0,
+        circle: 0,
+    }
+}
+
+// Only the low 8 layers are shown, the rest are left as they are
+fn layer_checkboxes(ui: &mut egui::Ui, bits: &mut u32) {
+    for bit in 0..8 {
+        let mut set = *bits & (1 << bit) != 0;
+        if ui
+            .checkbox(&mut set, "")
+            .on_hover_text(format!("Layer {}", bit + 1))
+            .changed()
+        {
+            *bits ^= 1 << bit;
+        }
+    }
+}
+
+// Bump this whenever a change to `App` would make old saved state load wrong, saved state from another version
+// is thrown away instead of being loaded
+const APP_SCHEMA_VERSION: u32 = 1;
+
 #[derive(Serialize, Deserialize)]
 pub struct App {
+    schema_version: u32,
     #[serde(skip, default = "std::time::Instant::now")]
     last_time: std::time::Instant,
     #[serde(skip)]
@@ -29,31 +266,129 @@ pub struct App {
     info_window_open: bool,
     settings_window_open: bool,
     quads_window_open: bool,
+    #[serde(skip)]
+    command_palette_open: bool,
+    #[serde(skip)]
+    command_palette_query: String,
     physics_enabled: bool,
-    sweeping_colliders: bool,
-    gravity: cgmath::Vector2<f32>,
+    // Fixed updates requested with the step controls, only run while physics is paused
+    #[serde(skip)]
+    pending_steps: usize,
+    lockstep: bool,
+    // How fast simulated time passes compared to real time, the fixed update interval stays the same
+    time_scale: f32,
+    physics_hz: f32,
+    pause_when_unfocused: bool,
+    #[serde(skip)]
+    paused_for_focus: bool,
+    camera_locked: bool,
+    spawn_mode: bool,
+    camera_shake_enabled: bool,
+    camera_shake_threshold: f32,
+    #[serde(skip)]
+    camera_shake_time: f32,
+    letterbox_aspect: Option<f32>,
+    show_contact_counts: bool,
+    debug_draw: bool,
+    show_velocities: bool,
+    // Scales both the velocity arrows and the angular velocity arcs
+    velocity_arrow_scale: f32,
+    clear_color: cgmath::Vector3<f32>,
+    color_by: ColorBy,
+    // The speed that is drawn fully red when coloring by speed
+    color_by_max_speed: f32,
+    grid_enabled: bool,
+    grid_spacing: f32,
+    // The renderer falls back to a lower count if this one isnt supported
+    sample_count: u32,
+    warm_up_steps: usize,
     camera: Camera,
-    quads: Vec<Quad>,
-    old_quads: Vec<Quad>,
+    world: PhysicsWorld,
+    // The quads from when physics was first enabled, or when the checkpoint was last set
+    #[serde(skip)]
+    checkpoint: Option<Vec<Quad>>,
+    textures: Vec<String>,
+    #[serde(skip)]
+    texture_rects: Vec<Result<TextureRect, String>>,
+    #[serde(skip)]
+    texture_path: String,
+    screenshot_path: String,
+    #[serde(skip)]
+    screenshot_error: Option<String>,
+    #[serde(skip)]
+    scene_path: String,
+    // Shown in a window until it is dismissed
+    #[serde(skip)]
+    scene_error: Option<String>,
+    // The last one is the one Tab moves on from
+    selected: Vec<usize>,
+    // The quad the camera moves towards every frame, panning stops following it
+    follow_quad: Option<usize>,
+    follow_stiffness: f32,
+    // The aspect ratio of the view from the last frame
+    #[serde(skip)]
+    view_aspect: Option<f32>,
+    #[serde(skip)]
+    grabbed_quad: Option<GrabbedQuad>,
+    // The quad being pulled back with the right mouse button, it is launched the other way on release
+    #[serde(skip)]
+    slingshot_quad: Option<usize>,
+    #[serde(skip)]
+    undo_history: Vec<EditSnapshot>,
+    #[serde(skip)]
+    redo_history: Vec<EditSnapshot>,
+    // From before the edit in the quads window that is still being dragged or typed into, taken when the mouse is
+    // pressed or a widget has focus
+    #[serde(skip)]
+    edit_start: Option<EditSnapshot>,
+    impulse: cgmath::Vector2<f32>,
+    duplicate_offset: cgmath::Vector2<f32>,
+    duplicate_columns: usize,
+    duplicate_rows: usize,
+    // From the center of one copy to the next, so the quad's scale makes them touch
+    duplicate_spacing: cgmath::Vector2<f32>,
+    throw_on_release: bool,
+    #[serde(skip)]
+    scroll_to_selected_quad: bool,
 }
 
 impl Default for App {
     fn default() -> Self {
         Self {
+            schema_version: APP_SCHEMA_VERSION,
             last_time: std::time::Instant::now(),
             fixed_update_time: std::time::Duration::ZERO,
             info_window_open: false,
             settings_window_open: false,
             quads_window_open: false,
+            command_palette_open: false,
+            command_palette_query: String::new(),
             physics_enabled: false,
-            sweeping_colliders: false,
-            gravity: cgmath::vec2(0.0, -9.81),
-            camera: Camera {
-                position: cgmath::vec2(0.0, 0.0),
-                rotation: 0.0,
-                zoom: 0.25,
-            },
-            quads: vec![
+            pending_steps: 0,
+            lockstep: false,
+            time_scale: 1.0,
+            physics_hz: 100.0,
+            pause_when_unfocused: true,
+            paused_for_focus: false,
+            camera_locked: false,
+            spawn_mode: false,
+            camera_shake_enabled: false,
+            camera_shake_threshold: 5.0,
+            camera_shake_time: 0.0,
+            letterbox_aspect: None,
+            show_contact_counts: false,
+            debug_draw: false,
+            show_velocities: false,
+            velocity_arrow_scale: 0.5,
+            clear_color: cgmath::vec3(0.2, 0.2, 0.2),
+            color_by: ColorBy::None,
+            color_by_max_speed: 10.0,
+            grid_enabled: false,
+            grid_spacing: 1.0,
+            sample_count: 4,
+            warm_up_steps: 0,
+            camera: Camera::default(),
+            world: PhysicsWorld::new(vec![
                 Quad {
                     position: cgmath::vec2(0.0, 0.0),
                     velocity: cgmath::vec2(0.0, 0.0),
@@ -61,7 +396,28 @@ impl Default for App {
                     angular_velocity: 0.0,
                     scale: cgmath::vec2(1.0, 1.0),
                     color: cgmath::vec3(0.1, 0.2, 0.8),
-                    dynamic: true,
+                    alpha: 1.0,
+                    body_type: BodyType::Dynamic,
+                    mass: 1.0,
+                    drag_coefficient: 0.0,
+                    gravity_scale: 1.0,
+                    linear_damping: None,
+                    angular_damping: None,
+                    friction: 0.5,
+                    render_order: 0,
+                    texture: None,
+                    initial: None,
+                    flash_on_collision: false,
+                    layer: 1,
+                    mask: u32::MAX,
+                    is_sensor: false,
+                    one_way: false,
+                    one_way_normal: cgmath::vec2(0.0, 1.0),
+                    flash_time: 0.0,
+                    penetration_depth: 0.0,
+                    stuck_steps: 0,
+                    is_sleeping: false,
+                    low_energy_steps: 0,
                 },
                 Quad {
                     position: cgmath::vec2(0.0, -2.0),
@@ -70,10 +426,54 @@ impl Default for App {
                     angular_velocity: 0.0,
                     scale: cgmath::vec2(5.0, 0.5),
                     color: cgmath::vec3(0.3, 0.8, 0.2),
-                    dynamic: false,
+                    alpha: 1.0,
+                    body_type: BodyType::Static,
+                    mass: 1.0,
+                    drag_coefficient: 0.0,
+                    gravity_scale: 1.0,
+                    linear_damping: None,
+                    angular_damping: None,
+                    friction: 0.5,
+                    render_order: 0,
+                    texture: None,
+                    initial: None,
+                    flash_on_collision: false,
+                    layer: 1,
+                    mask: u32::MAX,
+                    is_sensor: false,
+                    one_way: false,
+                    one_way_normal: cgmath::vec2(0.0, 1.0),
+                    flash_time: 0.0,
+                    penetration_depth: 0.0,
+                    stuck_steps: 0,
+                    is_sleeping: false,
+                    low_energy_steps: 0,
                 },
-            ],
-            old_quads: vec![],
+            ]),
+            checkpoint: None,
+            textures: vec![],
+            texture_rects: vec![],
+            texture_path: String::new(),
+            screenshot_path: String::new(),
+            screenshot_error: None,
+            scene_path: String::new(),
+            scene_error: None,
+            selected: vec![],
+            follow_quad: None,
+            follow_stiffness: 5.0,
+            view_aspect: None,
+            grabbed_quad: None,
+            slingshot_quad: None,
+            undo_history: vec![],
+            redo_history: vec![],
+            edit_start: None,
+            impulse: cgmath::vec2(0.0, 5.0),
+            duplicate_offset: cgmath::vec2(0.5, 0.5),
+            duplicate_columns: 5,
+            duplicate_rows: 5,
+            duplicate_spacing: cgmath::vec2(1.0, 1.0),
+            throw_on_release: true,
+            scroll_to_selected_quad: false,
         }
     }
 }
@@ -95,123 +495,256 @@ impl App {
             assert!(old_value.is_none());
         }
 
-        cc.storage
-            .map(|s| serde_json::from_str(s.get_string("App").as_deref().unwrap_or("")))
-            .transpose()
-            .ok()
-            .flatten()
-            .unwrap_or_default()
+        // Check the version first, saved state from another version could still parse but with the wrong meaning
+        let mut app: Self = cc
+            .storage
+            .and_then(|s| s.get_string("App"))
+            .and_then(|json| serde_json::from_str::<serde_json::Value>(&json).ok())
+            .filter(|value| {
+                value
+                    .get("schema_version")
+                    .and_then(serde_json::Value::as_u64)
+                    == Some(APP_SCHEMA_VERSION as u64)
+            })
+            .and_then(|value| serde_json::from_value(value).ok())
+            .unwrap_or_default();
+
+        // Let the scene settle before the first frame is shown
+        let fixed_update_interval = app.fixed_update_interval();
+        for _ in 0..app.warm_up_steps {
+            app.fixed_update(fixed_update_interval.as_secs_f32());
+        }
+
+        app
     }
 
-    fn update(&mut self, _ts: f32) {}
+    fn fixed_update_interval(&self) -> std::time::Duration {
+        std::time::Duration::from_secs_f64(1.0 / self.physics_hz as f64)
+    }
 
-    fn fixed_update(&mut self, ts: f32) {
-        self.quads
-            .par_iter_mut()
-            .filter(|quad| quad.dynamic)
-            .for_each(|quad| {
-                quad.velocity += self.gravity * ts;
-            });
+    fn update(&mut self, ts: f32) {
+        if let Some(quad) = self
+            .follow_quad
+            .and_then(|index| self.world.quads.get(index))
+        {
+            // Frame rate independent, the camera covers the same fraction of the distance each second
+            let t = 1.0 - (-self.follow_stiffness * ts).exp();
+            self.camera.position = self.camera.position.lerp(quad.position, t);
+        }
+    }
 
-        let solved = AtomicBool::new(false);
-        let mut iterations = 0;
-        while !solved.load(Ordering::Relaxed) && iterations < MAX_PHYSICS_ITERATIONS {
-            solved.store(true, Ordering::Relaxed);
-
-            std::mem::swap(&mut self.quads, &mut self.old_quads);
-            self.quads.clear();
-            self.quads.reserve(self.old_quads.len());
-            self.quads
-                .par_extend(
-                    self.old_quads
-                        .par_iter()
-                        .enumerate()
-                        .map(|(index, &(mut quad))| {
-                            if quad.dynamic {
-                                let mut position_delta = cgmath::vec2(0.0, 0.0);
-                                let mut velocity_delta = cgmath::vec2(0.0, 0.0);
-
-                                // TODO: spacial hashing so we dont have to iterate through every object in the scene
-                                for (other_index, other) in self.old_quads.iter().enumerate() {
-                                    if other_index != index {
-                                        let sweeping_collider = SweepingCollider {
-                                            collider: &quad,
-                                            position_a: quad.position,
-                                            position_b: (quad.position + position_delta)
-                                                + (quad.velocity + velocity_delta) * ts,
-                                        };
-
-                                        let sweeping_collider_other = SweepingCollider {
-                                            collider: other,
-                                            position_a: other.position,
-                                            position_b: other.position + other.velocity * ts,
-                                        };
-
-                                        let (collider_a, collider_b): (
-                                            &dyn Collider,
-                                            &dyn Collider,
-                                        ) = if self.sweeping_colliders {
-                                            (&sweeping_collider, &sweeping_collider_other)
-                                        } else {
-                                            (&quad, other)
-                                        };
-
-                                        if let Some(collision) =
-                                            get_collision(collider_a, collider_b)
-                                        {
-                                            let relative_velocity = other.velocity - quad.velocity;
-                                            let collision_normal_velocity_length =
-                                                relative_velocity.dot(-collision.normal);
-                                            if collision_normal_velocity_length >= 0.0 {
-                                                // A collision has happened, so the physics is not solved
-                                                solved.store(false, Ordering::Relaxed);
-
-                                                let dynamic_count =
-                                                    quad.dynamic as usize + other.dynamic as usize;
-
-                                                if let Some(collision) = get_collision(&quad, other)
-                                                {
-                                                    // Move the quad out of collision
-                                                    position_delta -= collision.normal
-                                                        * collision.depth
-                                                        / dynamic_count as _;
-                                                }
-
-                                                // Stop movement in that direction
-                                                velocity_delta -= (-relative_velocity)
-                                                    .dot(collision.normal)
-                                                    * collision.normal;
-                                            }
-                                        }
-                                    }
-                                }
+    fn scene(&self) -> Scene {
+        Scene {
+            version: SCENE_VERSION,
+            quads: self.world.quads.clone(),
+            capsules: self.world.capsules.clone(),
+            joints: self.world.joints.clone(),
+            springs: self.world.springs.clone(),
+            gravity: self.world.gravity,
+            camera: self.camera,
+        }
+    }
 
-                                quad.position += position_delta;
-                                quad.velocity += velocity_delta;
-                            }
-                            quad
-                        }),
-                );
+    fn load_scene(&mut self, scene: Scene) {
+        self.world.quads = scene.quads;
+        self.world.capsules = scene.capsules;
+        self.world.joints = scene.joints;
+        self.world.springs = scene.springs;
+        self.world.gravity = scene.gravity;
+        self.camera = scene.camera;
+        // Anything that refers to quads by index is about the old scene
+        self.world.clear_contacts();
+        self.selected.clear();
+        self.follow_quad = None;
+        self.grabbed_quad = None;
+        self.slingshot_quad = None;
+        // The old scene's edits would be undone on top of the new one
+        self.undo_history.clear();
+        self.redo_history.clear();
+        self.edit_start = None;
+    }
 
-            iterations += 1;
+    fn snapshot(&self) -> EditSnapshot {
+        EditSnapshot {
+            quads: self.world.quads.clone(),
+            joints: self.world.joints.clone(),
+            springs: self.world.springs.clone(),
         }
+    }
 
-        if iterations == MAX_PHYSICS_ITERATIONS {
-            println!("Warning: reached maximum physics iterations, the simulation may be unstable");
+    // Call with the snapshot from before an edit, edits made while the physics is running arent recorded
+    fn push_undo(&mut self, snapshot: EditSnapshot) {
+        // An edit in the quads window that is still going ends here, otherwise it would be pushed again from before
+        // this edit once it is let go
+        if let Some(edit_start) = self.edit_start.take() {
+            if edit_start != snapshot {
+                self.push_undo(edit_start);
+            }
         }
+        if self.physics_enabled || snapshot == self.snapshot() {
+            return;
+        }
+        if self.undo_history.len() >= MAX_UNDO_HISTORY {
+            self.undo_history.remove(0);
+        }
+        self.undo_history.push(snapshot);
+        self.redo_history.clear();
+    }
 
-        self.quads
-            .par_iter_mut()
-            .filter(|quad| quad.dynamic)
-            .for_each(|quad| {
-                quad.position += quad.velocity * ts;
-                quad.rotation += quad.angular_velocity * ts;
-            });
+    // Returns what was replaced so it can be pushed onto the other history
+    fn restore(&mut self, snapshot: EditSnapshot) -> EditSnapshot {
+        let current = self.snapshot();
+        self.world.quads = snapshot.quads;
+        self.world.joints = snapshot.joints;
+        self.world.springs = snapshot.springs;
+        self.world.clear_contacts();
+        let quad_count = self.world.quads.len();
+        self.selected.retain(|&index| index < quad_count);
+        self.follow_quad = self
+            .follow_quad
+            .filter(|&index| index < self.world.quads.len());
+        self.grabbed_quad = None;
+        self.slingshot_quad = None;
+        self.edit_start = None;
+        current
+    }
+
+    // Gives the grabbed quads back the body types they had before they were grabbed
+    fn release_grabbed_quads(grabbed_quad: &mut Option<GrabbedQuad>, quads: &mut [Quad]) {
+        let Some(grabbed) = grabbed_quad.take() else { return; };
+        for (index, body_type) in grabbed.body_types {
+            if let Some(quad) = quads.get_mut(index) {
+                quad.body_type = body_type;
+            }
+        }
+    }
+
+    // Undoing while the physics is running would throw away the simulation
+    fn undo(&mut self) {
+        if self.physics_enabled {
+            return;
+        }
+        if let Some(snapshot) = self.undo_history.pop() {
+            let current = self.restore(snapshot);
+            self.redo_history.push(current);
+        }
+    }
+
+    fn redo(&mut self) {
+        if self.physics_enabled {
+            return;
+        }
+        if let Some(snapshot) = self.redo_history.pop() {
+            let current = self.restore(snapshot);
+            self.undo_history.push(current);
+        }
+    }
+
+    fn reset_to_checkpoint(&mut self) {
+        let Some(checkpoint) = &self.checkpoint else { return; };
+        self.world.quads = checkpoint.clone();
+        self.world.clear_contacts();
+        self.fixed_update_time = std::time::Duration::ZERO;
+        self.physics_enabled = false;
+        let quad_count = self.world.quads.len();
+        self.selected.retain(|&index| index < quad_count);
+        self.follow_quad = self
+            .follow_quad
+            .filter(|&index| index < self.world.quads.len());
+        self.grabbed_quad = None;
+        self.slingshot_quad = None;
+    }
+
+    fn reset_quads(quads: &mut [Quad]) {
+        for quad in quads {
+            if let Some(initial) = quad.initial {
+                quad.set_state(initial);
+            }
+        }
+    }
+
+    fn run_command(&mut self, command: Command) {
+        match command {
+            Command::AddQuad => {
+                self.push_undo(self.snapshot());
+                self.world.quads.push(Quad::default());
+            }
+            Command::AddCapsule => self.world.capsules.push(Capsule::default()),
+            Command::SeparateOverlaps => {
+                self.push_undo(self.snapshot());
+                PhysicsWorld::separate_overlaps(
+                    &mut self.world.quads,
+                    &self.world.collision_params,
+                );
+            }
+            Command::ResetQuads => {
+                self.push_undo(self.snapshot());
+                Self::reset_quads(&mut self.world.quads);
+            }
+            Command::ResetToCheckpoint => self.reset_to_checkpoint(),
+            Command::ResetCamera => self.camera = Camera::default(),
+            Command::TogglePhysics => self.physics_enabled = !self.physics_enabled,
+            Command::ToggleLockstep => self.lockstep = !self.lockstep,
+            Command::Step => self.pending_steps += 1,
+            Command::ToggleCameraLock => self.camera_locked = !self.camera_locked,
+            Command::ToggleSpawnMode => self.spawn_mode = !self.spawn_mode,
+            Command::ToggleContactCounts => self.show_contact_counts = !self.show_contact_counts,
+            Command::ToggleInfoWindow => self.info_window_open = !self.info_window_open,
+            Command::ToggleSettingsWindow => {
+                self.settings_window_open = !self.settings_window_open;
+            }
+            Command::ToggleQuadsWindow => self.quads_window_open = !self.quads_window_open,
+            Command::FrameAll => {
+                let aabb = self
+                    .world
+                    .quads
+                    .iter()
+                    .map(Collider::aabb)
+                    .chain(self.world.capsules.iter().map(Collider::aabb))
+                    .reduce(|a, b| a.union(&b));
+                self.frame_camera(aabb);
+            }
+            Command::FrameSelected => {
+                let aabb = self
+                    .selected
+                    .iter()
+                    .filter_map(|&index| self.world.quads.get(index))
+                    .map(Collider::aabb)
+                    .reduce(|a, b| a.union(&b));
+                self.frame_camera(aabb);
+            }
+            // The copies become the selection, so duplicating again copies the copies
+            Command::DuplicateSelected => {
+                self.push_undo(self.snapshot());
+                let first_copy = self.world.quads.len();
+                for &index in &self.selected {
+                    let copy = duplicate(&self.world.quads[index], self.duplicate_offset);
+                    self.world.quads.push(copy);
+                }
+                self.selected = (first_copy..self.world.quads.len()).collect();
+            }
+            Command::Undo => self.undo(),
+            Command::Redo => self.redo(),
+        }
+    }
+
+    // Does nothing if there is nothing to frame or the view hasnt been drawn yet
+    fn frame_camera(&mut self, aabb: Option<Aabb>) {
+        if let (Some(aabb), Some(aspect)) = (aabb, self.view_aspect) {
+            self.camera.frame(aabb, aspect);
+            self.follow_quad = None;
+        }
+    }
+
+    fn fixed_update(&mut self, ts: f32) {
+        self.world.lod_center = self.camera.position;
+        self.world.record_collisions = self.debug_draw;
+        self.world.step(ts);
     }
 }
 
 impl eframe::App for App {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
         let time = std::time::Instant::now();
         let dt = time.duration_since(self.last_time);
         self.last_time = time;
@@ -220,24 +753,92 @@ impl eframe::App for App {
 
         Self::update(self, ts);
 
+        // Upload any textures that were added since the last frame, or that were loaded from storage
+        if self.texture_rects.len() < self.textures.len() {
+            let render_state = frame.wgpu_render_state().unwrap();
+            let mut renderer = render_state.renderer.write();
+            let renderer: &mut Renderer = renderer.paint_callback_resources.get_mut().unwrap();
+            for path in &self.textures[self.texture_rects.len()..] {
+                let rect = load_png(path).and_then(|texture| {
+                    renderer
+                        .add_texture(&render_state.queue, &texture)
+                        .ok_or_else(|| "not enough space in the texture atlas".to_string())
+                });
+                self.texture_rects.push(rect);
+            }
+        }
+
+        if self.physics_enabled && self.checkpoint.is_none() {
+            self.checkpoint = Some(self.world.quads.clone());
+        }
+
         let fixed_update_start = std::time::Instant::now();
-        if self.physics_enabled {
-            self.fixed_update_time += dt;
-            let fixed_update_interval = std::time::Duration::from_secs_f64(1.0 / 100.0);
-            while self.fixed_update_time > fixed_update_interval {
+        let fixed_update_count_start = self.world.step_count();
+        let paused_for_focus = self.pause_when_unfocused && !ctx.input(|i| i.raw.has_focus);
+        // No frames are drawn while paused, so the first frame back would try to catch up on all that time
+        let resumed_from_focus_pause = self.paused_for_focus && !paused_for_focus;
+        self.paused_for_focus = paused_for_focus;
+        // A time scale of 0 is the same as pausing, even in lockstep
+        if self.physics_enabled && !paused_for_focus && self.time_scale > 0.0 {
+            let fixed_update_interval = self.fixed_update_interval();
+            if self.lockstep {
+                // Advance by exactly one fixed step per frame, ignoring how much real time passed
+                self.fixed_update(fixed_update_interval.as_secs_f32());
+                self.fixed_update_time = std::time::Duration::ZERO;
+            } else {
+                if !resumed_from_focus_pause {
+                    self.fixed_update_time += dt.mul_f32(self.time_scale);
+                }
+                let mut fixed_updates = 0;
+                while self.fixed_update_time > fixed_update_interval {
+                    // If physics cant keep up, catching up would make the next frame even slower,
+                    // so drop the time it is behind by and let the simulation slow down instead
+                    if fixed_updates == MAX_FIXED_UPDATES_PER_FRAME {
+                        self.fixed_update_time = std::time::Duration::ZERO;
+                        break;
+                    }
+                    self.fixed_update(fixed_update_interval.as_secs_f32());
+                    self.fixed_update_time -= fixed_update_interval;
+                    fixed_updates += 1;
+                }
+            }
+        } else {
+            // Stepping ignores real time, so the same steps always give the same result
+            let fixed_update_interval = self.fixed_update_interval();
+            for _ in 0..self.pending_steps {
                 self.fixed_update(fixed_update_interval.as_secs_f32());
-                self.fixed_update_time -= fixed_update_interval;
             }
         }
+        self.pending_steps = 0;
         let fixed_update_duration = fixed_update_start.elapsed();
+        let fixed_updates = self.world.step_count() - fixed_update_count_start;
 
         // Make sure rotations dont get too high
-        self.quads.par_iter_mut().for_each(|quad| {
+        self.world.quads.par_iter_mut().for_each(|quad| {
             quad.rotation %= std::f32::consts::TAU;
             quad.rotation += std::f32::consts::TAU;
             quad.rotation %= std::f32::consts::TAU;
         });
 
+        self.world.quads.par_iter_mut().for_each(|quad| {
+            quad.flash_time = (quad.flash_time - ts).max(0.0);
+        });
+
+        // Shake the camera if the last step had a big enough impact
+        self.camera_shake_time = (self.camera_shake_time - ts).max(0.0);
+        if self.camera_shake_enabled && fixed_updates > 0 {
+            let max_impulse = self
+                .world
+                .contact_impulses()
+                .iter()
+                .flatten()
+                .map(|&(_, impulses, _)| impulses.iter().sum::<f32>())
+                .fold(0.0, f32::max);
+            if max_impulse > self.camera_shake_threshold {
+                self.camera_shake_time = CAMERA_SHAKE_DURATION;
+            }
+        }
+
         egui::TopBottomPanel::top("Top Panel").show(ctx, |ui| {
             ui.horizontal(|ui| {
                 self.info_window_open |= ui.button("Info").clicked();
@@ -246,6 +847,15 @@ impl eframe::App for App {
             });
         });
 
+        let renderer_stats = frame.wgpu_render_state().map(|render_state| {
+            let renderer = render_state.renderer.read();
+            let renderer: &Renderer = renderer.paint_callback_resources.get().unwrap();
+            renderer.stats()
+        });
+
+        let estimated_memory = self.world.estimated_memory();
+        let color_legend = self.color_by.legend(self.color_by_max_speed);
+        let fixed_ts = self.fixed_update_interval().as_secs_f32();
         egui::Window::new("Info")
             .open(&mut self.info_window_open)
             .show(ctx, |ui| {
@@ -255,56 +865,1158 @@ impl eframe::App for App {
                     "Fixed Update Time: {:.3}ms",
                     fixed_update_duration.as_secs_f32() * 1000.0
                 ));
+                ui.label(format!(
+                    "Fixed Updates This Frame: {fixed_updates} ({:.1}/s)",
+                    fixed_updates as f32 / ts
+                ));
+                if self.physics_enabled {
+                    // If stepping takes longer than the time it simulates, it can never catch up
+                    if fixed_update_duration > dt {
+                        ui.colored_label(egui::Color32::YELLOW, "Physics is falling behind");
+                    } else {
+                        ui.label("Physics is keeping up");
+                    }
+                }
+                ui.label(format!(
+                    "Estimated Physics Memory: {:.1} KiB",
+                    estimated_memory as f32 / 1024.0
+                ));
+                if let Some(stats) = renderer_stats {
+                    ui.label(format!("Quad Instances: {}", stats.quad_count));
+                    ui.label(format!(
+                        "Quad Storage Buffer Capacity: {} bytes",
+                        stats.quad_storage_buffer_capacity
+                    ));
+                    ui.label(format!(
+                        "Reallocated Last Frame: {}",
+                        stats.reallocated_last_prepare
+                    ));
+                }
+                if !color_legend.is_empty() {
+                    ui.label("Colors:");
+                    for (label, color) in &color_legend {
+                        let [r, g, b] = (*color * 255.0).map(|channel| channel as u8).into();
+                        ui.colored_label(egui::Color32::from_rgb(r, g, b), label);
+                    }
+                }
+                let report = self.world.last_step_report();
+                ui.label(format!("Solver Iterations: {}", report.iterations));
+                ui.label(format!("Contacts: {}", report.contact_count));
+                if report.hit_iteration_cap {
+                    ui.colored_label(
+                        egui::Color32::YELLOW,
+                        "The last step reached the iteration limit, the simulation may be unstable",
+                    );
+                }
+                if report.collision_iteration_cap_hits > 0 {
+                    ui.colored_label(
+                        egui::Color32::YELLOW,
+                        format!(
+                            "Collision detection gave up {} times in the last step",
+                            report.collision_iteration_cap_hits
+                        ),
+                    );
+                }
+                let island_iterations = self.world.island_iterations();
+                if !island_iterations.is_empty() {
+                    egui::CollapsingHeader::new(format!(
+                        "Islands: {} (most iterations: {})",
+                        island_iterations.len(),
+                        island_iterations.iter().max().unwrap()
+                    ))
+                    .id_source("Islands")
+                    .show(ui, |ui| {
+                        for (i, iterations) in island_iterations.iter().enumerate() {
+                            ui.label(format!("Island {i}: {iterations} iterations"));
+                        }
+                    });
+                }
+                if !self.world.trigger_events().is_empty() {
+                    ui.label(format!("Trigger Events: {}", self.world.trigger_events().len()));
+                    for &(a, b) in self.world.trigger_events() {
+                        ui.label(format!("Quad {a} overlaps Quad {b}"));
+                    }
+                }
+                if self.world.sleeping_enabled {
+                    let sleeping = self.world.quads.iter().filter(|quad| quad.is_sleeping).count();
+                    let dynamic = self
+                        .world
+                        .quads
+                        .iter()
+                        .filter(|quad| quad.body_type == BodyType::Dynamic)
+                        .count();
+                    ui.label(format!("Sleeping Quads: {sleeping}/{dynamic}"));
+                }
+                for (i, spring) in self.world.springs.iter().enumerate() {
+                    let inverse_mass = [spring.body_a, spring.body_b]
+                        .into_iter()
+                        .filter_map(|index| Some(self.world.quads.get(index)?.inverse_mass()))
+                        .sum::<f32>();
+                    let max_stiffness = SpringJoint::max_stiffness(inverse_mass, fixed_ts);
+                    if spring.stiffness > max_stiffness {
+                        ui.colored_label(
+                            egui::Color32::YELLOW,
+                            format!("Spring {i} is too stiff for the physics rate, it is clamped to {max_stiffness:.0}"),
+                        );
+                    }
+                }
+                for (i, quad) in self.world.quads.iter().enumerate() {
+                    if quad.stuck_steps >= STUCK_STEPS_WARNING {
+                        ui.colored_label(
+                            egui::Color32::YELLOW,
+                            format!(
+                                "Quad {i} stuck in penetration for {} steps",
+                                quad.stuck_steps
+                            ),
+                        );
+                    }
+                }
                 ui.allocate_space(ui.available_size());
             });
 
+        if let Some(error) = &self.scene_error {
+            let mut dismissed = false;
+            egui::Window::new("Scene Error")
+                .collapsible(false)
+                .resizable(false)
+                .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+                .show(ctx, |ui| {
+                    ui.colored_label(egui::Color32::RED, error);
+                    dismissed = ui.button("Ok").clicked();
+                });
+            if dismissed {
+                self.scene_error = None;
+            }
+        }
+
+        // The window borrows `self`, so resetting happens after it
+        let mut reset_to_checkpoint = false;
+        let mut reset_saved_state = false;
+        let mut frame_command = None;
         egui::Window::new("Settings")
             .open(&mut self.settings_window_open)
             .show(ctx, |ui| {
                 ui.horizontal(|ui| {
-                    ui.label("Physics Enabled: ");
-                    ui.checkbox(&mut self.physics_enabled, "");
+                    ui.label("Physics Enabled: ");
+                    ui.checkbox(&mut self.physics_enabled, "");
+                    ui.add_enabled_ui(!self.physics_enabled, |ui| {
+                        if ui.button("Step").on_hover_text("Run one fixed update, also bound to the period key").clicked() {
+                            self.pending_steps += 1;
+                        }
+                        if ui.button("Step 10").clicked() {
+                            self.pending_steps += 10;
+                        }
+                    });
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Lockstep: ");
+                    ui.checkbox(&mut self.lockstep, "")
+                        .on_hover_text("Run exactly one fixed update per frame instead of keeping up with real time, useful for recording");
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Time Scale: ");
+                    ui.add(egui::Slider::new(&mut self.time_scale, 0.0..=4.0))
+                        .on_hover_text("Slow motion below 1, 0 pauses");
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Pause When Unfocused: ");
+                    ui.checkbox(&mut self.pause_when_unfocused, "");
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Spawn Mode: ");
+                    ui.checkbox(&mut self.spawn_mode, "")
+                        .on_hover_text("Clicking in the view adds a quad there instead of selecting one");
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Throw on Release: ");
+                    ui.checkbox(&mut self.throw_on_release, "")
+                        .on_hover_text("Quads that are let go of while dragging them keep moving at the speed they were dragged");
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Lock Camera: ");
+                    ui.checkbox(&mut self.camera_locked, "");
+                });
+                ui.horizontal(|ui| {
+                    if ui.button("Frame All").clicked() {
+                        frame_command = Some(Command::FrameAll);
+                    }
+                    if ui
+                        .add_enabled(!self.selected.is_empty(), egui::Button::new("Frame Selected"))
+                        .clicked()
+                    {
+                        frame_command = Some(Command::FrameSelected);
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Follow Stiffness: ");
+                    ui.add(
+                        egui::DragValue::new(&mut self.follow_stiffness)
+                            .speed(0.1)
+                            .clamp_range(0.0..=f32::INFINITY),
+                    )
+                    .on_hover_text("How quickly the camera catches up to the followed quad");
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Camera Shake: ");
+                    ui.checkbox(&mut self.camera_shake_enabled, "")
+                        .on_hover_text("Shake the camera when a contact impulse is bigger than the threshold");
+                    if self.camera_shake_enabled {
+                        ui.add(
+                            egui::DragValue::new(&mut self.camera_shake_threshold)
+                                .speed(0.1)
+                                .clamp_range(0.0..=f32::INFINITY)
+                                .prefix("threshold: "),
+                        );
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Gravity: ");
+                    ui.add(
+                        egui::DragValue::new(&mut self.world.gravity.x)
+                            .speed(0.1)
+                            .prefix("x: "),
+                    );
+                    ui.add(
+                        egui::DragValue::new(&mut self.world.gravity.y)
+                            .speed(0.1)
+                            .prefix("y: "),
+                    );
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Gravity Axes: ");
+                    ui.radio_value(&mut self.world.gravity_axes, GravityAxes::Both, "Both");
+                    ui.radio_value(&mut self.world.gravity_axes, GravityAxes::XOnly, "X Only");
+                    ui.radio_value(&mut self.world.gravity_axes, GravityAxes::YOnly, "Y Only");
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Apply Gravity: ");
+                    ui.radio_value(
+                        &mut self.world.gravity_integration,
+                        GravityIntegration::BeforeSolve,
+                        "Before Solve",
+                    );
+                    ui.radio_value(
+                        &mut self.world.gravity_integration,
+                        GravityIntegration::AfterSolve,
+                        "After Solve",
+                    );
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Sweeping Colliders: ");
+                    egui::ComboBox::from_id_source("Sweeping Colliders")
+                        .selected_text(self.world.sweeping_mode.name())
+                        .show_ui(ui, |ui| {
+                            for mode in SweepingMode::ALL {
+                                ui.selectable_value(&mut self.world.sweeping_mode, mode, mode.name());
+                            }
+                        });
+                    if self.world.sweeping_mode == SweepingMode::Automatic {
+                        ui.add(
+                            egui::DragValue::new(&mut self.world.sweeping_threshold)
+                                .speed(0.01)
+                                .clamp_range(0.0..=f32::INFINITY)
+                                .prefix("threshold: "),
+                        )
+                        .on_hover_text("Sweep a pair when either quad moves further than this fraction of its smallest side in one step");
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Relaxation: ");
+                    ui.add(egui::Slider::new(&mut self.world.relaxation, 1.0..=2.0))
+                        .on_hover_text("Scales every correction the solver makes, values above 1 make stacks converge in fewer iterations but can overshoot and become unstable");
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Contact Resolution: ");
+                    ui.radio_value(
+                        &mut self.world.contact_resolution,
+                        ContactResolution::Simultaneous,
+                        "Simultaneous",
+                    );
+                    ui.radio_value(
+                        &mut self.world.contact_resolution,
+                        ContactResolution::DeepestFirst,
+                        "Deepest First",
+                    )
+                    .on_hover_text("Only resolve the deepest contact of each quad per iteration, more stable for wedged quads but slower to converge");
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Broadphase Cell Size: ");
+                    ui.add(
+                        egui::DragValue::new(&mut self.world.broadphase_cell_size)
+                            .speed(0.1)
+                            .clamp_range(0.1..=f32::INFINITY),
+                    )
+                    .on_hover_text("Quads only check for collisions with quads in the same cells of this size, around the size of a typical quad is fastest");
+                });
+                ui.horizontal(|ui| {
+                    ui.label("EPA Tolerance: ");
+                    ui.add(
+                        egui::DragValue::new(&mut self.world.collision_params.epa_tolerance)
+                            .speed(0.0001)
+                            .clamp_range(0.000001..=f32::INFINITY),
+                    )
+                    .on_hover_text("Smaller is more accurate for tiny quads, bigger converges faster for huge quads");
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Collision Iterations: ");
+                    ui.add(
+                        egui::DragValue::new(&mut self.world.collision_params.max_iterations)
+                            .clamp_range(1..=usize::MAX),
+                    );
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Position Correction: ");
+                    ui.add(
+                        egui::Slider::new(
+                            &mut self.world.collision_params.position_correction_factor,
+                            0.01..=1.0,
+                        ),
+                    )
+                    .on_hover_text("How much of a deep overlap is pushed out each step, smaller settles overlapping quads more gently");
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Penetration Slop: ");
+                    ui.add(
+                        egui::DragValue::new(&mut self.world.collision_params.penetration_slop)
+                            .speed(0.001)
+                            .clamp_range(0.0..=f32::INFINITY),
+                    )
+                    .on_hover_text("Overlaps shallower than this are pushed out in one step like any other contact");
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Ignore Internal Edges: ");
+                    ui.checkbox(&mut self.world.ignore_internal_edges, "")
+                        .on_hover_text("Ignore contacts on the seams between touching static quads, so quads slide smoothly over tiled floors");
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Velocity Scale: ");
+                    ui.add(
+                        egui::DragValue::new(&mut self.world.velocity_scale)
+                            .speed(0.001)
+                            .clamp_range(0.0..=2.0),
+                    );
+                    if ui.button("Apply Once").clicked() {
+                        for quad in self
+                            .world
+                            .quads
+                            .iter_mut()
+                            .filter(|quad| quad.body_type == BodyType::Dynamic)
+                        {
+                            quad.velocity *= self.world.velocity_scale;
+                            quad.angular_velocity *= self.world.velocity_scale;
+                        }
+                    }
+                    ui.checkbox(&mut self.world.continuous_velocity_scale, "Every Second");
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Linear Damping: ");
+                    ui.add(egui::Slider::new(&mut self.world.linear_damping, 0.0..=5.0))
+                        .on_hover_text("How much of its velocity each quad loses per second");
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Angular Damping: ");
+                    ui.add(egui::Slider::new(&mut self.world.angular_damping, 0.0..=5.0))
+                        .on_hover_text("How much of its angular velocity each quad loses per second");
+                });
+                if self.world.continuous_velocity_scale && self.world.velocity_scale > 1.0 {
+                    ui.colored_label(
+                        egui::Color32::YELLOW,
+                        "A velocity scale above 1 adds energy, this is not physical",
+                    );
+                }
+                ui.horizontal(|ui| {
+                    ui.label("Symmetry Jitter: ");
+                    ui.checkbox(&mut self.world.jitter_enabled, "")
+                        .on_hover_text("Slightly perturb collision normals to break perfectly symmetric balances, the same seed always gives the same result");
+                    if self.world.jitter_enabled {
+                        ui.add(egui::DragValue::new(&mut self.world.jitter_seed).prefix("seed: "));
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Warm Starting: ");
+                    ui.checkbox(&mut self.world.warm_starting, "")
+                        .on_hover_text("Start each contact from the impulses it had at the end of the last step, helps stacks settle in fewer iterations");
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Physics Rate: ");
+                    ui.add(
+                        egui::DragValue::new(&mut self.physics_hz)
+                            .clamp_range(1.0..=1000.0)
+                            .suffix("Hz"),
+                    )
+                    .on_hover_text(format!("Higher rates are more stable but cost more per second of simulation, at most {MAX_FIXED_UPDATES_PER_FRAME} fixed updates run per frame so slow frames make the simulation slow down"));
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Warm-up Steps: ");
+                    ui.add(egui::DragValue::new(&mut self.warm_up_steps))
+                        .on_hover_text("Fixed updates to run when the app starts, before the first frame is shown");
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Level of Detail: ");
+                    ui.checkbox(&mut self.world.lod_enabled, "")
+                        .on_hover_text("Step quads that are far from the camera and barely moving less often");
+                    if self.world.lod_enabled {
+                        ui.add(
+                            egui::DragValue::new(&mut self.world.lod_interval)
+                                .clamp_range(1..=u64::MAX)
+                                .prefix("every "),
+                        );
+                        ui.add(
+                            egui::DragValue::new(&mut self.world.lod_distance)
+                                .speed(0.1)
+                                .prefix("distance: "),
+                        );
+                        ui.add(
+                            egui::DragValue::new(&mut self.world.lod_speed)
+                                .speed(0.01)
+                                .prefix("speed: "),
+                        );
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Sleeping: ");
+                    ui.checkbox(&mut self.world.sleeping_enabled, "")
+                        .on_hover_text("Stop stepping quads that have been barely moving for a while");
+                    if self.world.sleeping_enabled {
+                        ui.add(
+                            egui::DragValue::new(&mut self.world.sleep_threshold)
+                                .speed(0.01)
+                                .clamp_range(0.0..=f32::INFINITY)
+                                .prefix("threshold: "),
+                        );
+                        ui.add(
+                            egui::DragValue::new(&mut self.world.sleep_steps)
+                                .prefix("after: ")
+                                .suffix(" steps"),
+                        );
+                    }
+                });
+                if ui
+                    .button("Reset")
+                    .on_hover_text("Restore every quad that has an initial state to it")
+                    .clicked()
+                {
+                    Self::reset_quads(&mut self.world.quads);
+                }
+                ui.horizontal(|ui| {
+                    if ui
+                        .button("Set Checkpoint")
+                        .on_hover_text("Remember the quads as they are now, this also happens the first time physics is enabled")
+                        .clicked()
+                    {
+                        self.checkpoint = Some(self.world.quads.clone());
+                    }
+                    reset_to_checkpoint = ui
+                        .add_enabled(self.checkpoint.is_some(), egui::Button::new("Reset to Checkpoint"))
+                        .on_hover_text("Put the quads back to the checkpoint and pause physics")
+                        .clicked();
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Background Color: ");
+                    let mut rgb = self.clear_color.into();
+                    egui::color_picker::color_edit_button_rgb(ui, &mut rgb);
+                    self.clear_color = rgb.into();
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Color By: ");
+                    egui::ComboBox::from_id_source("Color By")
+                        .selected_text(self.color_by.name())
+                        .show_ui(ui, |ui| {
+                            for color_by in ColorBy::ALL {
+                                ui.selectable_value(&mut self.color_by, color_by, color_by.name());
+                            }
+                        });
+                    if self.color_by == ColorBy::Speed {
+                        ui.add(
+                            egui::DragValue::new(&mut self.color_by_max_speed)
+                                .speed(0.1)
+                                .clamp_range(0.01..=f32::INFINITY)
+                                .prefix("max speed: "),
+                        )
+                        .on_hover_text("Quads moving this fast or faster are drawn fully red");
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Grid: ");
+                    ui.checkbox(&mut self.grid_enabled, "");
+                    if self.grid_enabled {
+                        ui.add(
+                            egui::DragValue::new(&mut self.grid_spacing)
+                                .speed(0.1)
+                                .clamp_range(0.01..=f32::INFINITY)
+                                .prefix("spacing: "),
+                        )
+                        .on_hover_text("The distance between the minor lines, every 5th line is a major line");
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("MSAA: ");
+                    egui::ComboBox::from_id_source("MSAA")
+                        .selected_text(format!("{}x", self.sample_count))
+                        .show_ui(ui, |ui| {
+                            for sample_count in [1, 2, 4, 8] {
+                                ui.selectable_value(
+                                    &mut self.sample_count,
+                                    sample_count,
+                                    format!("{sample_count}x"),
+                                );
+                            }
+                        });
+                    if let Some(stats) = renderer_stats {
+                        if stats.sample_count != self.sample_count {
+                            ui.label(format!("(not supported, using {}x)", stats.sample_count));
+                        }
+                    }
                 });
                 ui.horizontal(|ui| {
-                    ui.label("Gravity: ");
-                    ui.add(
-                        egui::DragValue::new(&mut self.gravity.x)
-                            .speed(0.1)
-                            .prefix("x: "),
-                    );
-                    ui.add(
-                        egui::DragValue::new(&mut self.gravity.y)
-                            .speed(0.1)
-                            .prefix("y: "),
+                    ui.label("Show Contact Counts: ");
+                    ui.checkbox(&mut self.show_contact_counts, "");
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Debug Draw: ");
+                    ui.checkbox(&mut self.debug_draw, "").on_hover_text(
+                        "Draw the contact points and normals of the collisions from the last step, the normals are scaled by the depth",
                     );
                 });
                 ui.horizontal(|ui| {
-                    ui.label("Sweeping Colliders: ");
-                    ui.checkbox(&mut self.sweeping_colliders, "");
+                    ui.label("Show Velocities: ");
+                    ui.checkbox(&mut self.show_velocities, "");
+                    if self.show_velocities {
+                        ui.add(
+                            egui::DragValue::new(&mut self.velocity_arrow_scale)
+                                .speed(0.01)
+                                .clamp_range(0.0..=f32::INFINITY)
+                                .prefix("scale: "),
+                        );
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Letterbox: ");
+                    let mut letterbox = self.letterbox_aspect.is_some();
+                    ui.checkbox(&mut letterbox, "");
+                    match (letterbox, &mut self.letterbox_aspect) {
+                        (true, None) => self.letterbox_aspect = Some(16.0 / 9.0),
+                        (false, Some(_)) => self.letterbox_aspect = None,
+                        (true, Some(aspect)) => {
+                            ui.add(
+                                egui::DragValue::new(aspect)
+                                    .speed(0.01)
+                                    .clamp_range(0.1..=10.0)
+                                    .prefix("aspect: "),
+                            );
+                        }
+                        (false, None) => {}
+                    }
                 });
+                reset_saved_state = ui
+                    .button("Reset All Saved State")
+                    .on_hover_text("Forget everything that was saved and go back to how the app first starts, this cant be undone")
+                    .clicked();
                 ui.allocate_space(ui.available_size());
             });
 
+        if reset_to_checkpoint {
+            self.reset_to_checkpoint();
+        }
+        if reset_saved_state {
+            *self = Self::default();
+            if let Some(storage) = frame.storage_mut() {
+                storage.set_string("App", String::new());
+                storage.flush();
+            }
+            // Nothing else this frame should see the old state, so start again from the next frame
+            ctx.request_repaint();
+            return;
+        }
+        if let Some(command) = frame_command {
+            self.run_command(command);
+        }
+
+        let mut command_to_run = None;
+        let mut close_command_palette = false;
+        egui::Window::new("Command Palette")
+            .open(&mut self.command_palette_open)
+            .collapsible(false)
+            .anchor(egui::Align2::CENTER_TOP, egui::vec2(0.0, 32.0))
+            .show(ctx, |ui| {
+                let response = ui.text_edit_singleline(&mut self.command_palette_query);
+                response.request_focus();
+                let mut commands = Command::ALL
+                    .into_iter()
+                    .filter(|command| fuzzy_match(&self.command_palette_query, command.name()))
+                    .peekable();
+                if ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                    command_to_run = commands.peek().copied();
+                }
+                for command in commands {
+                    if ui.button(command.name()).clicked() {
+                        command_to_run = Some(command);
+                    }
+                }
+                close_command_palette = ui.input(|i| i.key_pressed(egui::Key::Escape));
+            });
+        if let Some(command) = command_to_run {
+            self.run_command(command);
+            close_command_palette = true;
+        }
+        if close_command_palette {
+            self.command_palette_open = false;
+        }
+
+        // The window borrows `self`, so the scene is saved and loaded after it
+        let mut save_scene = false;
+        let mut take_screenshot = false;
+        let mut load_scene = false;
+        let mut undo = false;
+        let mut redo = false;
+        // Only record an edit once nothing is being dragged or typed into, so a drag is one undo instead of one per
+        // frame. This is when a DragValue is released or loses focus, and it also catches checkboxes, combo boxes
+        // and the color picker. The quads are only copied when an edit could be starting, not every frame
+        let editing = |ctx: &egui::Context| {
+            ctx.input(|i| i.pointer.any_down()) || ctx.memory(|m| m.focus().is_some())
+        };
+        let can_edit = self.quads_window_open && !self.physics_enabled;
+        if can_edit && self.edit_start.is_none() && editing(ctx) {
+            self.edit_start = Some(self.snapshot());
+        }
         egui::Window::new("Quads")
             .open(&mut self.quads_window_open)
             .show(ctx, |ui| {
                 egui::ScrollArea::vertical().show(ui, |ui| {
-                    if ui.button("Add Quad").clicked() {
-                        self.quads.push(Quad::default());
+                    ui.horizontal(|ui| {
+                        if ui.button("Add Quad").clicked() {
+                            self.world.quads.push(Quad::default());
+                        }
+                        if ui.button("Add Capsule").clicked() {
+                            self.world.capsules.push(Capsule::default());
+                        }
+                        if ui.button("Add Spring").clicked() {
+                            self.world.springs.push(SpringJoint {
+                                body_a: 0,
+                                body_b: 1,
+                                rest_length: 1.0,
+                                stiffness: 100.0,
+                                damping: 1.0,
+                            });
+                        }
+                        if ui.button("Add Joint").clicked() {
+                            self.world.joints.push(Joint {
+                                body_a: 0,
+                                body_b: 1,
+                                anchor_a: cgmath::vec2(0.0, 0.0),
+                                anchor_b: cgmath::vec2(0.0, 0.0),
+                                rest_length: 1.0,
+                            });
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        undo = ui
+                            .add_enabled(
+                                !self.physics_enabled && !self.undo_history.is_empty(),
+                                egui::Button::new("Undo"),
+                            )
+                            .clicked();
+                        redo = ui
+                            .add_enabled(
+                                !self.physics_enabled && !self.redo_history.is_empty(),
+                                egui::Button::new("Redo"),
+                            )
+                            .clicked();
+                    });
+                    if ui
+                        .add_enabled(
+                            !self.physics_enabled,
+                            egui::Button::new("Separate Overlaps"),
+                        )
+                        .on_hover_text(
+                            "Push overlapping quads apart before starting the simulation",
+                        )
+                        .clicked()
+                    {
+                        PhysicsWorld::separate_overlaps(
+                            &mut self.world.quads,
+                            &self.world.collision_params,
+                        );
+                    }
+                    ui.horizontal(|ui| {
+                        ui.text_edit_singleline(&mut self.scene_path);
+                        save_scene =
+                            ui.button("Save Scene").clicked() && !self.scene_path.is_empty();
+                        load_scene =
+                            ui.button("Load Scene").clicked() && !self.scene_path.is_empty();
+                    });
+                    ui.horizontal(|ui| {
+                        ui.text_edit_singleline(&mut self.texture_path);
+                        if ui.button("Load Texture").clicked() && !self.texture_path.is_empty() {
+                            self.textures.push(std::mem::take(&mut self.texture_path));
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.text_edit_singleline(&mut self.screenshot_path);
+                        take_screenshot = ui
+                            .button("Screenshot")
+                            .on_hover_text("Save the last drawn frame of the view as a png")
+                            .clicked()
+                            && !self.screenshot_path.is_empty();
+                    });
+                    if let Some(error) = &self.screenshot_error {
+                        ui.colored_label(egui::Color32::RED, error);
+                    }
+                    for (path, rect) in self.textures.iter().zip(&self.texture_rects) {
+                        if let Err(error) = rect {
+                            ui.colored_label(
+                                egui::Color32::RED,
+                                format!("Failed to load '{path}': {error}"),
+                            );
+                        }
                     }
+                    ui.horizontal(|ui| {
+                        ui.label("Duplicate Offset: ");
+                        ui.add(
+                            egui::DragValue::new(&mut self.duplicate_offset.x)
+                                .speed(0.1)
+                                .prefix("x: "),
+                        );
+                        ui.add(
+                            egui::DragValue::new(&mut self.duplicate_offset.y)
+                                .speed(0.1)
+                                .prefix("y: "),
+                        );
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Duplicate Grid: ");
+                        ui.add(
+                            egui::DragValue::new(&mut self.duplicate_columns)
+                                .clamp_range(1..=100)
+                                .prefix("columns: "),
+                        );
+                        ui.add(
+                            egui::DragValue::new(&mut self.duplicate_rows)
+                                .clamp_range(1..=100)
+                                .prefix("rows: "),
+                        );
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Grid Spacing: ");
+                        ui.add(
+                            egui::DragValue::new(&mut self.duplicate_spacing.x)
+                                .speed(0.1)
+                                .prefix("x: "),
+                        );
+                        ui.add(
+                            egui::DragValue::new(&mut self.duplicate_spacing.y)
+                                .speed(0.1)
+                                .prefix("y: "),
+                        );
+                    });
                     let mut quads_to_delete = vec![];
-                    for i in 0..self.quads.len() {
-                        egui::CollapsingHeader::new(format!("Quad {i}")).show(ui, |ui| {
-                            let quad = &mut self.quads[i];
+                    self.selected
+                        .retain(|&index| index < self.world.quads.len());
+                    if let Some(&last) = self.selected.last() {
+                        ui.separator();
+                        ui.horizontal(|ui| {
+                            ui.label(format!("{} Selected", self.selected.len()));
+                            if ui.button("Delete Selected").clicked() {
+                                quads_to_delete.extend_from_slice(&self.selected);
+                            }
+                            if ui.button("Clear Selection").clicked() {
+                                self.selected.clear();
+                            }
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Set Body Type: ");
+                            for body_type in BodyType::ALL {
+                                if ui.button(body_type.name()).clicked() {
+                                    for &index in &self.selected {
+                                        self.world.quads[index].body_type = body_type;
+                                        self.world.quads[index].wake();
+                                    }
+                                }
+                            }
+                        });
+                        // Moving the last selected quad moves all of them by the same amount
+                        let old_position = self.world.quads[last].position;
+                        let mut position = old_position;
+                        ui.horizontal(|ui| {
+                            ui.label("Move Together: ");
+                            ui.add(
+                                egui::DragValue::new(&mut position.x)
+                                    .speed(0.1)
+                                    .prefix("x: "),
+                            );
+                            ui.add(
+                                egui::DragValue::new(&mut position.y)
+                                    .speed(0.1)
+                                    .prefix("y: "),
+                            );
+                        });
+                        if position != old_position {
+                            for &index in &self.selected {
+                                self.world.quads[index].position += position - old_position;
+                                self.world.quads[index].wake();
+                            }
+                        }
+                        let mut rgb = self.world.quads[last].color.into();
+                        ui.horizontal(|ui| {
+                            ui.label("Color: ");
+                            if egui::color_picker::color_edit_button_rgb(ui, &mut rgb).changed() {
+                                for &index in &self.selected {
+                                    self.world.quads[index].color = rgb.into();
+                                }
+                            }
+                        });
+                        ui.separator();
+                    }
+                    for i in 0..self.world.quads.len() {
+                        let selected = self.selected.contains(&i);
+                        let scroll_to =
+                            self.selected.last() == Some(&i) && self.scroll_to_selected_quad;
+                        let mut title = egui::RichText::new(format!("Quad {i}"));
+                        if selected {
+                            title = title.strong();
+                        }
+                        let mut header =
+                            egui::collapsing_header::CollapsingState::load_with_default_open(
+                                ui.ctx(),
+                                ui.make_persistent_id(format!("Quad {i}")),
+                                false,
+                            );
+                        if scroll_to {
+                            header.set_open(true);
+                        }
+                        let (_, header_response, _) = header
+                            .show_header(ui, |ui| {
+                                let mut checked = selected;
+                                if ui.checkbox(&mut checked, title).changed() {
+                                    if checked {
+                                        self.selected.push(i);
+                                    } else {
+                                        self.selected.retain(|&index| index != i);
+                                    }
+                                }
+                            })
+                            .body(|ui| {
+                                let quad = &mut self.world.quads[i];
+                                let old_state = quad.state();
+                                ui.horizontal(|ui| {
+                                    ui.label("Position: ");
+                                    ui.add(
+                                        egui::DragValue::new(&mut quad.position.x)
+                                            .speed(0.1)
+                                            .prefix("x: "),
+                                    );
+                                    ui.add(
+                                        egui::DragValue::new(&mut quad.position.y)
+                                            .speed(0.1)
+                                            .prefix("y: "),
+                                    );
+                                });
+                                ui.horizontal(|ui| {
+                                    ui.label("Velocity: ");
+                                    ui.add(
+                                        egui::DragValue::new(&mut quad.velocity.x)
+                                            .speed(0.1)
+                                            .prefix("x: ")
+                                            .suffix("m/s"),
+                                    );
+                                    ui.add(
+                                        egui::DragValue::new(&mut quad.velocity.y)
+                                            .speed(0.1)
+                                            .prefix("y: ")
+                                            .suffix("m/s"),
+                                    );
+                                });
+                                ui.horizontal(|ui| {
+                                    ui.label("Rotation: ");
+                                    ui.drag_angle(&mut quad.rotation);
+                                });
+                                ui.horizontal(|ui| {
+                                    ui.label("Angular Velocity: ");
+                                    // Copied from egui::ui::Ui::drag_angle
+                                    pub fn drag_angle(
+                                        ui: &mut egui::Ui,
+                                        radians: &mut f32,
+                                    ) -> egui::Response {
+                                        let mut degrees = radians.to_degrees();
+                                        let mut response = ui.add(
+                                            egui::DragValue::new(&mut degrees)
+                                                .speed(1.0)
+                                                .suffix("°/s"),
+                                        );
+
+                                        // only touch `*radians` if we actually changed the degree value
+                                        if degrees != radians.to_degrees() {
+                                            *radians = degrees.to_radians();
+                                            response.changed = true;
+                                        }
+
+                                        response
+                                    }
+                                    drag_angle(ui, &mut quad.angular_velocity);
+                                });
+                                // A sleeping quad would ignore the new state until something hit it
+                                if quad.state() != old_state {
+                                    quad.wake();
+                                }
+                                ui.horizontal(|ui| {
+                                    ui.label("Scale: ");
+                                    ui.add(
+                                        egui::DragValue::new(&mut quad.scale.x)
+                                            .speed(0.1)
+                                            .prefix("x: "),
+                                    );
+                                    ui.add(
+                                        egui::DragValue::new(&mut quad.scale.y)
+                                            .speed(0.1)
+                                            .prefix("y: "),
+                                    );
+                                });
+                                ui.horizontal(|ui| {
+                                    ui.label("Color: ");
+                                    let mut rgb = quad.color.into();
+                                    egui::color_picker::color_edit_button_rgb(ui, &mut rgb);
+                                    quad.color = rgb.into();
+                                    ui.add(
+                                        egui::DragValue::new(&mut quad.alpha)
+                                            .speed(0.01)
+                                            .clamp_range(0.0..=1.0)
+                                            .prefix("alpha: "),
+                                    );
+                                });
+                                ui.horizontal(|ui| {
+                                    ui.label("Body Type: ");
+                                    egui::ComboBox::from_id_source(("Body Type", i))
+                                        .selected_text(quad.body_type.name())
+                                        .show_ui(ui, |ui| {
+                                            for body_type in BodyType::ALL {
+                                                ui.selectable_value(
+                                                    &mut quad.body_type,
+                                                    body_type,
+                                                    body_type.name(),
+                                                );
+                                            }
+                                        });
+                                });
+                                ui.horizontal(|ui| {
+                                    ui.label("Mass: ");
+                                    ui.add(
+                                        egui::DragValue::new(&mut quad.mass)
+                                            .speed(0.1)
+                                            .clamp_range(0.001..=f32::INFINITY),
+                                    );
+                                });
+                                ui.horizontal(|ui| {
+                                    ui.label("Drag Coefficient: ");
+                                    ui.add(
+                                        egui::DragValue::new(&mut quad.drag_coefficient)
+                                            .speed(0.01)
+                                            .clamp_range(0.0..=f32::INFINITY),
+                                    );
+                                });
+                                ui.horizontal(|ui| {
+                                    ui.label("Gravity Scale: ");
+                                    ui.add(
+                                        egui::DragValue::new(&mut quad.gravity_scale).speed(0.01),
+                                    )
+                                    .on_hover_text(
+                                        "0 ignores gravity, negative values float upwards",
+                                    );
+                                });
+                                for (label, damping, world_damping) in [
+                                    (
+                                        "Linear Damping: ",
+                                        &mut quad.linear_damping,
+                                        self.world.linear_damping,
+                                    ),
+                                    (
+                                        "Angular Damping: ",
+                                        &mut quad.angular_damping,
+                                        self.world.angular_damping,
+                                    ),
+                                ] {
+                                    ui.horizontal(|ui| {
+                                        ui.label(label);
+                                        let mut overridden = damping.is_some();
+                                        if ui
+                                            .checkbox(&mut overridden, "Override")
+                                            .on_hover_text(
+                                                "Use this instead of the damping in the settings",
+                                            )
+                                            .changed()
+                                        {
+                                            *damping = overridden.then_some(world_damping);
+                                        }
+                                        if let Some(damping) = damping {
+                                            ui.add(
+                                                egui::DragValue::new(damping)
+                                                    .speed(0.01)
+                                                    .clamp_range(0.0..=f32::INFINITY),
+                                            );
+                                        }
+                                    });
+                                }
+                                ui.horizontal(|ui| {
+                                    ui.label("Friction: ");
+                                    ui.add(
+                                        egui::DragValue::new(&mut quad.friction)
+                                            .speed(0.01)
+                                            .clamp_range(0.0..=f32::INFINITY),
+                                    );
+                                });
+                                ui.horizontal(|ui| {
+                                    ui.label("Flash on Collision: ");
+                                    ui.checkbox(&mut quad.flash_on_collision, "");
+                                });
+                                ui.horizontal(|ui| {
+                                    ui.label("Layer: ");
+                                    layer_checkboxes(ui, &mut quad.layer);
+                                });
+                                ui.horizontal(|ui| {
+                                    ui.label("Collides With: ");
+                                    layer_checkboxes(ui, &mut quad.mask);
+                                });
+                                ui.horizontal(|ui| {
+                                    ui.label("Sensor: ");
+                                    ui.checkbox(&mut quad.is_sensor, "");
+                                });
+                                ui.horizontal(|ui| {
+                                    ui.label("One Way: ");
+                                    ui.checkbox(&mut quad.one_way, "")
+                                        .on_hover_text("Quads can pass through from every side except the one the normal points to");
+                                    if quad.one_way {
+                                        ui.add(
+                                            egui::DragValue::new(&mut quad.one_way_normal.x)
+                                                .speed(0.01)
+                                                .prefix("x: "),
+                                        );
+                                        ui.add(
+                                            egui::DragValue::new(&mut quad.one_way_normal.y)
+                                                .speed(0.01)
+                                                .prefix("y: "),
+                                        );
+                                    }
+                                });
+                                ui.horizontal(|ui| {
+                                    ui.label("Texture: ");
+                                    egui::ComboBox::from_id_source(("Texture", i))
+                                        .selected_text(
+                                            quad.texture
+                                                .and_then(|texture| self.textures.get(texture))
+                                                .map_or("None", |path| path.as_str()),
+                                        )
+                                        .show_ui(ui, |ui| {
+                                            ui.selectable_value(&mut quad.texture, None, "None");
+                                            for (texture, path) in self.textures.iter().enumerate()
+                                            {
+                                                ui.selectable_value(
+                                                    &mut quad.texture,
+                                                    Some(texture),
+                                                    path,
+                                                );
+                                            }
+                                        });
+                                });
+                                ui.horizontal(|ui| {
+                                    ui.label("Render Order: ");
+                                    ui.add(egui::DragValue::new(&mut quad.render_order));
+                                });
+                                ui.horizontal(|ui| {
+                                    if ui.button("Set as Initial").clicked() {
+                                        quad.initial = Some(quad.state());
+                                    }
+                                    if quad.initial.is_some()
+                                        && ui.button("Clear Initial").clicked()
+                                    {
+                                        quad.initial = None;
+                                    }
+                                });
+                                ui.horizontal(|ui| {
+                                    ui.add(
+                                        egui::DragValue::new(&mut self.impulse.x)
+                                            .speed(0.1)
+                                            .prefix("x: "),
+                                    );
+                                    ui.add(
+                                        egui::DragValue::new(&mut self.impulse.y)
+                                            .speed(0.1)
+                                            .prefix("y: "),
+                                    );
+                                    if ui.button("Apply Impulse").clicked() {
+                                        quad.apply_impulse(self.impulse, quad.position);
+                                    }
+                                });
+                                let copies = ui
+                                    .horizontal(|ui| {
+                                        let mut copies = vec![];
+                                        if ui.button("Duplicate").clicked() {
+                                            copies.push(duplicate(quad, self.duplicate_offset));
+                                        }
+                                        if ui.button("Duplicate Grid").clicked() {
+                                            copies.extend(duplicate_grid(
+                                                quad,
+                                                self.duplicate_columns,
+                                                self.duplicate_rows,
+                                                self.duplicate_spacing,
+                                            ));
+                                        }
+                                        copies
+                                    })
+                                    .inner;
+                                self.world.quads.extend(copies);
+                                if self.follow_quad == Some(i) {
+                                    if ui.button("Stop Following").clicked() {
+                                        self.follow_quad = None;
+                                    }
+                                } else if ui.button("Follow").clicked() {
+                                    self.follow_quad = Some(i);
+                                }
+                                if ui.button("Delete").clicked() {
+                                    quads_to_delete.push(i);
+                                }
+                            });
+                        if scroll_to {
+                            header_response
+                                .response
+                                .scroll_to_me(Some(egui::Align::Center));
+                            self.scroll_to_selected_quad = false;
+                        }
+                    }
+
+                    // not sure that this sort is 100% nessaseary, they should be added in the order of the for loop
+                    quads_to_delete.sort();
+                    // A quad can be deleted both on its own and with the selection in the same frame
+                    quads_to_delete.dedup();
+                    // The grabbed quads are let go of before their indices change
+                    if !quads_to_delete.is_empty() {
+                        Self::release_grabbed_quads(&mut self.grabbed_quad, &mut self.world.quads);
+                    }
+                    // iterate backwards so that the indices dont get moved while removing
+                    for quad in quads_to_delete.into_iter().rev() {
+                        self.world.remove_quad(quad);
+                        self.selected.retain(|&selected| selected != quad);
+                        for selected in &mut self.selected {
+                            *selected -= (*selected > quad) as usize;
+                        }
+                        self.follow_quad = match self.follow_quad {
+                            Some(followed) if followed == quad => None,
+                            Some(followed) if followed > quad => Some(followed - 1),
+                            followed => followed,
+                        };
+                        self.slingshot_quad = None;
+                    }
+
+                    let mut capsule_to_delete = None;
+                    for (i, capsule) in self.world.capsules.iter_mut().enumerate() {
+                        egui::CollapsingHeader::new(format!("Capsule {i}")).show(ui, |ui| {
                             ui.horizontal(|ui| {
                                 ui.label("Position: ");
                                 ui.add(
-                                    egui::DragValue::new(&mut quad.position.x)
+                                    egui::DragValue::new(&mut capsule.position.x)
                                         .speed(0.1)
                                         .prefix("x: "),
                                 );
                                 ui.add(
-                                    egui::DragValue::new(&mut quad.position.y)
+                                    egui::DragValue::new(&mut capsule.position.y)
                                         .speed(0.1)
                                         .prefix("y: "),
                                 );
@@ -312,13 +2024,13 @@ impl eframe::App for App {
                             ui.horizontal(|ui| {
                                 ui.label("Velocity: ");
                                 ui.add(
-                                    egui::DragValue::new(&mut quad.velocity.x)
+                                    egui::DragValue::new(&mut capsule.velocity.x)
                                         .speed(0.1)
                                         .prefix("x: ")
                                         .suffix("m/s"),
                                 );
                                 ui.add(
-                                    egui::DragValue::new(&mut quad.velocity.y)
+                                    egui::DragValue::new(&mut capsule.velocity.y)
                                         .speed(0.1)
                                         .prefix("y: ")
                                         .suffix("m/s"),
@@ -326,100 +2038,385 @@ impl eframe::App for App {
                             });
                             ui.horizontal(|ui| {
                                 ui.label("Rotation: ");
-                                ui.drag_angle(&mut quad.rotation);
+                                ui.drag_angle(&mut capsule.rotation);
                             });
                             ui.horizontal(|ui| {
-                                ui.label("Angular Velocity: ");
-                                // Copied from egui::ui::Ui::drag_angle
-                                pub fn drag_angle(
-                                    ui: &mut egui::Ui,
-                                    radians: &mut f32,
-                                ) -> egui::Response {
-                                    let mut degrees = radians.to_degrees();
-                                    let mut response = ui.add(
-                                        egui::DragValue::new(&mut degrees).speed(1.0).suffix("°/s"),
-                                    );
-
-                                    // only touch `*radians` if we actually changed the degree value
-                                    if degrees != radians.to_degrees() {
-                                        *radians = degrees.to_radians();
-                                        response.changed = true;
+                                ui.label("Half Length: ");
+                                ui.add(
+                                    egui::DragValue::new(&mut capsule.half_length)
+                                        .speed(0.1)
+                                        .clamp_range(0.0..=f32::INFINITY),
+                                );
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("Radius: ");
+                                ui.add(
+                                    egui::DragValue::new(&mut capsule.radius)
+                                        .speed(0.1)
+                                        .clamp_range(0.001..=f32::INFINITY),
+                                );
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("Color: ");
+                                let mut rgb = capsule.color.into();
+                                egui::color_picker::color_edit_button_rgb(ui, &mut rgb);
+                                capsule.color = rgb.into();
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("Body Type: ");
+                                egui::ComboBox::from_id_source(("Capsule Body Type", i))
+                                    .selected_text(capsule.body_type.name())
+                                    .show_ui(ui, |ui| {
+                                        for body_type in BodyType::ALL {
+                                            ui.selectable_value(
+                                                &mut capsule.body_type,
+                                                body_type,
+                                                body_type.name(),
+                                            );
+                                        }
+                                    });
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("Mass: ");
+                                ui.add(
+                                    egui::DragValue::new(&mut capsule.mass)
+                                        .speed(0.1)
+                                        .clamp_range(0.001..=f32::INFINITY),
+                                );
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("Gravity Scale: ");
+                                ui.add(egui::DragValue::new(&mut capsule.gravity_scale).speed(0.01))
+                                    .on_hover_text("0 ignores gravity, negative values float upwards");
+                            });
+                            for (label, damping, world_damping) in [
+                                (
+                                    "Linear Damping: ",
+                                    &mut capsule.linear_damping,
+                                    self.world.linear_damping,
+                                ),
+                                (
+                                    "Angular Damping: ",
+                                    &mut capsule.angular_damping,
+                                    self.world.angular_damping,
+                                ),
+                            ] {
+                                ui.horizontal(|ui| {
+                                    ui.label(label);
+                                    let mut overridden = damping.is_some();
+                                    if ui
+                                        .checkbox(&mut overridden, "Override")
+                                        .on_hover_text("Use this instead of the damping in the settings")
+                                        .changed()
+                                    {
+                                        *damping = overridden.then_some(world_damping);
                                     }
+                                    if let Some(damping) = damping {
+                                        ui.add(
+                                            egui::DragValue::new(damping)
+                                                .speed(0.01)
+                                                .clamp_range(0.0..=f32::INFINITY),
+                                        );
+                                    }
+                                });
+                            }
+                            ui.horizontal(|ui| {
+                                ui.label("Layer: ");
+                                layer_checkboxes(ui, &mut capsule.layer);
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("Collides With: ");
+                                layer_checkboxes(ui, &mut capsule.mask);
+                            });
+                            if ui.button("Delete").clicked() {
+                                capsule_to_delete = Some(i);
+                            }
+                        });
+                    }
+                    if let Some(capsule) = capsule_to_delete {
+                        self.world.capsules.remove(capsule);
+                    }
 
-                                    response
-                                }
-                                drag_angle(ui, &mut quad.angular_velocity);
+                    let mut joint_to_delete = None;
+                    let last_quad = self.world.quads.len().saturating_sub(1);
+                    for (i, joint) in self.world.joints.iter_mut().enumerate() {
+                        egui::CollapsingHeader::new(format!("Joint {i}")).show(ui, |ui| {
+                            ui.horizontal(|ui| {
+                                ui.label("Quads: ");
+                                ui.add(
+                                    egui::DragValue::new(&mut joint.body_a)
+                                        .clamp_range(0..=last_quad)
+                                        .prefix("a: "),
+                                );
+                                ui.add(
+                                    egui::DragValue::new(&mut joint.body_b)
+                                        .clamp_range(0..=last_quad)
+                                        .prefix("b: "),
+                                );
                             });
                             ui.horizontal(|ui| {
-                                ui.label("Scale: ");
+                                ui.label("Anchor A: ");
                                 ui.add(
-                                    egui::DragValue::new(&mut quad.scale.x)
+                                    egui::DragValue::new(&mut joint.anchor_a.x)
                                         .speed(0.1)
                                         .prefix("x: "),
                                 );
                                 ui.add(
-                                    egui::DragValue::new(&mut quad.scale.y)
+                                    egui::DragValue::new(&mut joint.anchor_a.y)
                                         .speed(0.1)
                                         .prefix("y: "),
                                 );
                             });
                             ui.horizontal(|ui| {
-                                ui.label("Color: ");
-                                let mut rgb = quad.color.into();
-                                egui::color_picker::color_edit_button_rgb(ui, &mut rgb);
-                                quad.color = rgb.into();
+                                ui.label("Anchor B: ");
+                                ui.add(
+                                    egui::DragValue::new(&mut joint.anchor_b.x)
+                                        .speed(0.1)
+                                        .prefix("x: "),
+                                );
+                                ui.add(
+                                    egui::DragValue::new(&mut joint.anchor_b.y)
+                                        .speed(0.1)
+                                        .prefix("y: "),
+                                );
                             });
                             ui.horizontal(|ui| {
-                                ui.label("Dynamic: ");
-                                ui.checkbox(&mut quad.dynamic, "");
+                                ui.label("Rest Length: ");
+                                ui.add(
+                                    egui::DragValue::new(&mut joint.rest_length)
+                                        .speed(0.1)
+                                        .clamp_range(0.0..=f32::INFINITY),
+                                );
                             });
-                            if ui.button("Duplicate").clicked() {
-                                let mut copy = *quad;
-                                copy.position = cgmath::vec2(0.0, 0.0);
-                                copy.rotation = 0.0;
-                                self.quads.push(copy);
-                            }
                             if ui.button("Delete").clicked() {
-                                quads_to_delete.push(i);
+                                joint_to_delete = Some(i);
                             }
                         });
                     }
+                    if let Some(joint) = joint_to_delete {
+                        self.world.joints.remove(joint);
+                    }
 
-                    // not sure that this sort is 100% nessaseary, they should be added in the order of the for loop
-                    quads_to_delete.sort();
-                    // iterate backwards so that the indices dont get moved while removing
-                    for quad in quads_to_delete.into_iter().rev() {
-                        self.quads.remove(quad);
+                    let mut spring_to_delete = None;
+                    for (i, spring) in self.world.springs.iter_mut().enumerate() {
+                        egui::CollapsingHeader::new(format!("Spring {i}")).show(ui, |ui| {
+                            ui.horizontal(|ui| {
+                                ui.label("Quads: ");
+                                ui.add(
+                                    egui::DragValue::new(&mut spring.body_a)
+                                        .clamp_range(0..=last_quad)
+                                        .prefix("a: "),
+                                );
+                                ui.add(
+                                    egui::DragValue::new(&mut spring.body_b)
+                                        .clamp_range(0..=last_quad)
+                                        .prefix("b: "),
+                                );
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("Rest Length: ");
+                                ui.add(
+                                    egui::DragValue::new(&mut spring.rest_length)
+                                        .speed(0.1)
+                                        .clamp_range(0.0..=f32::INFINITY),
+                                );
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("Stiffness: ");
+                                ui.add(
+                                    egui::DragValue::new(&mut spring.stiffness)
+                                        .speed(1.0)
+                                        .clamp_range(0.0..=f32::INFINITY),
+                                );
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("Damping: ");
+                                ui.add(
+                                    egui::DragValue::new(&mut spring.damping)
+                                        .speed(0.1)
+                                        .clamp_range(0.0..=f32::INFINITY),
+                                );
+                            });
+                            if ui.button("Delete").clicked() {
+                                spring_to_delete = Some(i);
+                            }
+                        });
+                    }
+                    if let Some(spring) = spring_to_delete {
+                        self.world.springs.remove(spring);
                     }
 
                     ui.allocate_space(ui.available_size());
                 });
             });
+        if !can_edit {
+            self.edit_start = None;
+        } else if !editing(ctx) {
+            if let Some(edit_start) = self.edit_start.take() {
+                self.push_undo(edit_start);
+            }
+        }
+        if undo {
+            self.undo();
+        }
+        if redo {
+            self.redo();
+        }
+        if save_scene {
+            if let Err(error) = self.scene().save(&self.scene_path) {
+                self.scene_error = Some(format!("Failed to save '{}': {error}", self.scene_path));
+            }
+        }
+        if take_screenshot {
+            let render_state = frame.wgpu_render_state().unwrap();
+            let renderer = render_state.renderer.read();
+            let renderer: &Renderer = renderer.paint_callback_resources.get().unwrap();
+            self.screenshot_error = renderer
+                .screenshot(&render_state.device, &render_state.queue)
+                .and_then(|screenshot| save_png(&self.screenshot_path, &screenshot))
+                .err()
+                .map(|error| format!("Failed to save '{}': {error}", self.screenshot_path));
+        }
+        if load_scene {
+            match Scene::load(&self.scene_path) {
+                Ok(scene) => self.load_scene(scene),
+                Err(error) => {
+                    self.scene_error =
+                        Some(format!("Failed to load '{}': {error}", self.scene_path));
+                }
+            }
+        }
 
         let egui::InnerResponse {
             inner: (rect, response),
             ..
         } = egui::CentralPanel::default()
-            .frame(egui::Frame::none().fill(egui::Color32::from_rgb(51, 51, 51)))
+            .frame(egui::Frame::none())
             .show(ctx, |ui| {
                 let size = ui.available_size();
-                let (rect, response) = ui.allocate_exact_size(size, egui::Sense::click_and_drag());
+                let (full_rect, response) =
+                    ui.allocate_exact_size(size, egui::Sense::click_and_drag());
+
+                let rect = if let Some(target_aspect) = self.letterbox_aspect {
+                    // Render into the largest centered rect with the target aspect and fill the bars
+                    let size = if size.x / size.y > target_aspect {
+                        egui::vec2(size.y * target_aspect, size.y)
+                    } else {
+                        egui::vec2(size.x, size.x / target_aspect)
+                    };
+                    let rect = egui::Rect::from_center_size(full_rect.center(), size);
+                    ui.painter()
+                        .rect_filled(full_rect, 0.0, egui::Color32::BLACK);
+                    rect
+                } else {
+                    full_rect
+                };
+                let size = rect.size();
+                self.view_aspect = Some(size.x / size.y);
+                let pixel_size = size * ctx.pixels_per_point();
+                let pixel_size = [pixel_size.x.round() as u32, pixel_size.y.round() as u32];
+                let sample_count = self.sample_count;
 
+                // The shake is scaled by the zoom so it is always the same size on screen
+                let shake = self.camera_shake_time / CAMERA_SHAKE_DURATION * CAMERA_SHAKE_AMOUNT
+                    / self.camera.zoom;
+                let shake_offset = cgmath::vec2(
+                    (self.camera_shake_time * 60.0).sin(),
+                    (self.camera_shake_time * 47.0).cos(),
+                ) * shake;
                 let camera = CameraUniform {
-                    position: self.camera.position,
+                    position: self.camera.position + shake_offset,
                     rotation: self.camera.rotation,
                     zoom: self.camera.zoom,
                     screen_size: (size.x, size.y).into(),
                 };
-                let quads = self
+                let background = BackgroundUniform {
+                    color: self.clear_color,
+                };
+                let grid = self.grid_enabled.then_some(GridUniform {
+                    spacing: self.grid_spacing,
+                });
+                // Quads that are off screen are left out so big scenes only upload and draw what can be seen
+                let visible = Camera {
+                    position: camera.position,
+                    ..self.camera
+                }
+                .visible_aabb(rect);
+                let mut quads = self
+                    .world
                     .quads
                     .iter()
-                    .map(|quad| StorageBufferQuad {
-                        position: quad.position,
-                        scale: quad.scale,
-                        color: quad.color,
-                        rotation: quad.rotation,
+                    .filter(|quad| quad.aabb().intersects(&visible))
+                    .collect::<Vec<_>>();
+                // The sort is stable, so quads with the same render order are drawn in insertion order
+                quads.sort_by_key(|quad| quad.render_order);
+                let quads = quads
+                    .into_iter()
+                    .map(|quad| {
+                        let texture = quad
+                            .texture
+                            .and_then(|texture| self.texture_rects.get(texture)?.as_ref().ok());
+                        StorageBufferQuad {
+                            position: quad.position,
+                            scale: quad.scale,
+                            // Fade from white back to the quad's color after a new collision
+                            color: self
+                                .color_by
+                                .color(quad, self.color_by_max_speed)
+                                .unwrap_or(quad.color)
+                                .lerp(
+                                    cgmath::vec3(1.0, 1.0, 1.0),
+                                    quad.flash_time / FLASH_DURATION,
+                                )
+                                .extend(quad.alpha),
+                            rotation: quad.rotation,
+                            uv_offset: texture.map_or(cgmath::vec2(0.0, 0.0), |rect| rect.offset),
+                            uv_size: texture.map_or(cgmath::vec2(0.0, 0.0), |rect| rect.size),
+                            textured: texture.is_some() as u32,
+                            circle: 0,
+                        }
                     })
+                    .chain(self.world.capsules.iter().flat_map(|capsule| {
+                        let [a, b] = capsule.endpoints();
+                        let end = |position| StorageBufferQuad {
+                            position,
+                            scale: cgmath::vec2(1.0, 1.0) * capsule.radius * 2.0,
+                            color: capsule.color.extend(1.0),
+                            rotation: 0.0,
+                            uv_offset: cgmath::vec2(0.0, 0.0),
+                            uv_size: cgmath::vec2(0.0, 0.0),
+                            textured: 0,
+                            circle: 1,
+                        };
+                        [
+                            StorageBufferQuad {
+                                position: capsule.position,
+                                scale: cgmath::vec2(capsule.half_length, capsule.radius) * 2.0,
+                                color: capsule.color.extend(1.0),
+                                rotation: capsule.rotation,
+                                uv_offset: cgmath::vec2(0.0, 0.0),
+                                uv_size: cgmath::vec2(0.0, 0.0),
+                                textured: 0,
+                                circle: 0,
+                            },
+                            end(a),
+                            end(b),
+                        ]
+                    }))
+                    .chain(self.world.joints.iter().filter_map(|joint| {
+                        let a = self.world.quads.get(joint.body_a)?;
+                        let b = self.world.quads.get(joint.body_b)?;
+                        Some(rod(
+                            a.local_to_world(joint.anchor_a),
+                            b.local_to_world(joint.anchor_b),
+                            cgmath::vec3(0.5, 0.5, 0.5),
+                        ))
+                    }))
+                    .chain(self.world.springs.iter().filter_map(|spring| {
+                        let a = self.world.quads.get(spring.body_a)?;
+                        let b = self.world.quads.get(spring.body_b)?;
+                        Some(rod(a.position, b.position, cgmath::vec3(0.3, 0.8, 0.3)))
+                    }))
                     .collect::<Vec<_>>();
                 ui.painter().add(egui::PaintCallback {
                     rect,
@@ -427,7 +2424,11 @@ impl eframe::App for App {
                         eframe::egui_wgpu::CallbackFn::new()
                             .prepare(move |device, queue, encoder, data| {
                                 let renderer: &mut Renderer = data.get_mut().unwrap();
-                                renderer.prepare(camera, &quads, device, queue, encoder)
+                                renderer.set_sample_count(device, sample_count);
+                                renderer.prepare(
+                                    camera, background, grid, &quads, pixel_size, device, queue,
+                                    encoder,
+                                )
                             })
                             .paint(move |_info, render_pass, data| {
                                 let renderer: &Renderer = data.get().unwrap();
@@ -435,13 +2436,254 @@ impl eframe::App for App {
                             }),
                     ),
                 });
+
+                let painter = ui.painter_at(rect);
+                for quad in self
+                    .selected
+                    .iter()
+                    .filter_map(|&index| self.world.quads.get(index))
+                {
+                    let corners = quad.corners();
+                    // The corners are not in order around the quad
+                    let outline = [0, 2, 3, 1]
+                        .into_iter()
+                        .map(|corner| self.camera.world_to_screen(corners[corner], rect))
+                        .collect();
+                    painter.add(egui::Shape::closed_line(
+                        outline,
+                        egui::Stroke::new(2.0, egui::Color32::YELLOW),
+                    ));
+                }
+
+                if self.show_contact_counts {
+                    let painter = ui.painter_at(rect);
+                    for (quad, contacts) in
+                        self.world.quads.iter().zip(self.world.contact_impulses())
+                    {
+                        if !contacts.is_empty() {
+                            painter.text(
+                                self.camera.world_to_screen(quad.position, rect),
+                                egui::Align2::CENTER_CENTER,
+                                format!("{} contacts", contacts.len()),
+                                egui::FontId::default(),
+                                egui::Color32::WHITE,
+                            );
+                        }
+                    }
+                }
+
+                if self.debug_draw {
+                    let painter = ui.painter_at(rect);
+                    let stroke = egui::Stroke::new(1.5, egui::Color32::LIGHT_BLUE);
+                    for (quad, collisions) in self.world.quads.iter().zip(self.world.collisions()) {
+                        for collision in collisions {
+                            // The normal points away from the quad, towards the one it hit
+                            let normal = collision.normal * collision.depth * DEBUG_NORMAL_SCALE;
+                            let draw_normal = |point| {
+                                let start = self.camera.world_to_screen(point, rect);
+                                let end = self.camera.world_to_screen(point + normal, rect);
+                                painter.arrow(start, end - start, stroke);
+                            };
+                            if collision.contact_points.is_empty() {
+                                draw_normal(quad.position);
+                            }
+                            for &point in &collision.contact_points {
+                                draw_normal(point);
+                                painter.circle_filled(
+                                    self.camera.world_to_screen(point, rect),
+                                    3.0,
+                                    egui::Color32::RED,
+                                );
+                            }
+                        }
+                    }
+                }
+
+                if let (Some(quad), Some(cursor_pos)) = (
+                    self.slingshot_quad
+                        .and_then(|index| self.world.quads.get(index)),
+                    response.interact_pointer_pos(),
+                ) {
+                    ui.painter_at(rect).line_segment(
+                        [self.camera.world_to_screen(quad.position, rect), cursor_pos],
+                        egui::Stroke::new(2.0, egui::Color32::from_rgb(255, 128, 0)),
+                    );
+                }
+
+                if self.show_velocities {
+                    let painter = ui.painter_at(rect);
+                    let stroke = egui::Stroke::new(1.5, egui::Color32::GOLD);
+                    for quad in &self.world.quads {
+                        if quad.body_type != BodyType::Dynamic {
+                            continue;
+                        }
+
+                        let start = self.camera.world_to_screen(quad.position, rect);
+                        let end = self.camera.world_to_screen(
+                            quad.position + quad.velocity * self.velocity_arrow_scale,
+                            rect,
+                        );
+                        painter.arrow(start, end - start, stroke);
+
+                        // An arc around the center in the direction it is spinning, positive rotation is clockwise
+                        let sweep = (-quad.angular_velocity * self.velocity_arrow_scale)
+                            .clamp(-std::f32::consts::TAU, std::f32::consts::TAU);
+                        if sweep.abs() > 0.01 {
+                            let radius = quad.scale.x.min(quad.scale.y) * 0.5;
+                            let points = (0..=16)
+                                .map(|i| {
+                                    let angle = sweep * i as f32 / 16.0;
+                                    let offset = cgmath::vec2(angle.cos(), angle.sin()) * radius;
+                                    self.camera.world_to_screen(quad.position + offset, rect)
+                                })
+                                .collect();
+                            painter.add(egui::Shape::line(points, stroke));
+                        }
+                    }
+                }
+
                 (rect, response)
             });
 
-        {
+        if response.clicked() {
+            if let Some(cursor_pos) = response.interact_pointer_pos() {
+                let position = self.camera.screen_to_world(cursor_pos, rect);
+                if self.spawn_mode {
+                    self.world.quads.push(Quad {
+                        position,
+                        ..Quad::default()
+                    });
+                    self.selected = vec![self.world.quads.len() - 1];
+                    self.quads_window_open = true;
+                } else {
+                    // A ray with no length only hits the quads that it starts inside of
+                    let hit = raycast(
+                        &self.world.quads,
+                        position,
+                        cgmath::vec2(0.0, 1.0),
+                        0.0,
+                        &self.world.collision_params,
+                    )
+                    .map(|hit| hit.collider_index);
+                    // Shift adds or removes the quad from the selection instead of replacing it
+                    if ctx.input(|i| i.modifiers.shift) {
+                        if let Some(hit) = hit {
+                            if self.selected.contains(&hit) {
+                                self.selected.retain(|&index| index != hit);
+                            } else {
+                                self.selected.push(hit);
+                            }
+                        }
+                    } else {
+                        self.selected = hit.into_iter().collect();
+                    }
+                }
+                self.scroll_to_selected_quad = true;
+            }
+        }
+
+        if response.drag_started_by(egui::PointerButton::Secondary) {
+            if let Some(cursor_pos) = response.interact_pointer_pos() {
+                let position = self.camera.screen_to_world(cursor_pos, rect);
+                self.slingshot_quad = raycast(
+                    &self.world.quads,
+                    position,
+                    cgmath::vec2(0.0, 1.0),
+                    0.0,
+                    &self.world.collision_params,
+                )
+                .map(|hit| hit.collider_index);
+            }
+        }
+        if let Some(index) = self.slingshot_quad {
+            if response.drag_released_by(egui::PointerButton::Secondary) {
+                if let (Some(quad), Some(cursor_pos)) = (
+                    self.world.quads.get_mut(index),
+                    ctx.input(|i| i.pointer.interact_pos()),
+                ) {
+                    let cursor_world = self.camera.screen_to_world(cursor_pos, rect);
+                    let pulled_back = quad.position - cursor_world;
+                    quad.apply_impulse(pulled_back * SLINGSHOT_STRENGTH, quad.position);
+                }
+                self.slingshot_quad = None;
+            }
+        }
+
+        if response.drag_started_by(egui::PointerButton::Primary) && !self.spawn_mode {
+            if let Some(cursor_pos) = response.interact_pointer_pos() {
+                let position = self.camera.screen_to_world(cursor_pos, rect);
+                if let Some(hit) = raycast(
+                    &self.world.quads,
+                    position,
+                    cgmath::vec2(0.0, 1.0),
+                    0.0,
+                    &self.world.collision_params,
+                ) {
+                    // Grabbing a quad outside of the selection selects just that quad
+                    if !self.selected.contains(&hit.collider_index) {
+                        if !ctx.input(|i| i.modifiers.shift) {
+                            self.selected.clear();
+                        }
+                        self.selected.push(hit.collider_index);
+                    }
+                    let body_types = self
+                        .selected
+                        .iter()
+                        .map(|&index| {
+                            let quad = &mut self.world.quads[index];
+                            let body_type = quad.body_type;
+                            quad.body_type = BodyType::Kinematic;
+                            (index, body_type)
+                        })
+                        .collect();
+                    self.grabbed_quad = Some(GrabbedQuad {
+                        index: hit.collider_index,
+                        offset: self.world.quads[hit.collider_index].position - position,
+                        body_types,
+                        velocity: cgmath::vec2(0.0, 0.0),
+                    });
+                    self.scroll_to_selected_quad = true;
+                }
+            }
+        }
+        if let Some(grabbed) = &mut self.grabbed_quad {
+            if let Some(quad) = self.world.quads.get(grabbed.index) {
+                let mut movement = cgmath::vec2(0.0, 0.0);
+                if let Some(cursor_pos) = response.interact_pointer_pos() {
+                    let position = self.camera.screen_to_world(cursor_pos, rect) + grabbed.offset;
+                    movement = position - quad.position;
+                    if ts > 0.0 {
+                        grabbed.velocity = movement / ts;
+                    }
+                }
+                let released = response.drag_released();
+                for &(index, body_type) in &grabbed.body_types {
+                    let Some(quad) = self.world.quads.get_mut(index) else { continue; };
+                    quad.position += movement;
+                    quad.velocity = cgmath::vec2(0.0, 0.0);
+                    quad.angular_velocity = 0.0;
+                    quad.wake();
+
+                    if released {
+                        quad.body_type = body_type;
+                        if quad.body_type == BodyType::Dynamic && self.throw_on_release {
+                            quad.velocity = grabbed.velocity;
+                        }
+                    }
+                }
+                if released {
+                    self.grabbed_quad = None;
+                }
+            } else {
+                self.grabbed_quad = None;
+            }
+        }
+
+        if !self.camera_locked {
             let aspect = rect.width() / rect.height();
 
-            if response.dragged() {
+            if response.dragged() && self.grabbed_quad.is_none() && self.slingshot_quad.is_none() {
+                self.follow_quad = None;
                 let movement = response.drag_delta()
                     / self.camera.zoom
                     / (rect.size() * egui::vec2(0.5 / aspect, 0.5));
@@ -455,6 +2697,13 @@ impl eframe::App for App {
 
                     if i.any_touches() {
                         self.camera.zoom *= i.zoom_delta();
+                    } else if i.modifiers.command {
+                        // egui turns ctrl+scroll into a zoom event, use it for fine zooming
+                        if i.zoom_delta() > 1.0 {
+                            self.camera.zoom /= 0.99;
+                        } else if i.zoom_delta() < 1.0 {
+                            self.camera.zoom *= 0.99;
+                        }
                     }
                     if i.scroll_delta.y > 0.0 {
                         self.camera.zoom /= 0.9;
@@ -476,17 +2725,54 @@ impl eframe::App for App {
 
         if !ctx.wants_keyboard_input() {
             ctx.input(|i| {
+                if i.modifiers.command && i.key_pressed(egui::Key::P) {
+                    self.command_palette_open = true;
+                    self.command_palette_query.clear();
+                }
+                if i.modifiers.command && i.key_pressed(egui::Key::Z) {
+                    if i.modifiers.shift {
+                        self.redo();
+                    } else {
+                        self.undo();
+                    }
+                }
+                if i.modifiers.command && i.key_pressed(egui::Key::D) {
+                    self.run_command(Command::DuplicateSelected);
+                }
                 if i.key_pressed(egui::Key::Space) {
                     self.physics_enabled = !self.physics_enabled;
                 }
+                // egui has no key for the period, so look for it being typed instead
+                if !self.physics_enabled
+                    && i.events
+                        .iter()
+                        .any(|event| matches!(event, egui::Event::Text(text) if text == "."))
+                {
+                    self.pending_steps += 1;
+                }
+                if i.key_pressed(egui::Key::Tab) && !self.world.quads.is_empty() {
+                    let count = self.world.quads.len();
+                    self.selected = vec![match self.selected.last() {
+                        Some(&selected) if i.modifiers.shift => (selected + count - 1) % count,
+                        Some(&selected) => (selected + 1) % count,
+                        None if i.modifiers.shift => count - 1,
+                        None => 0,
+                    }];
+                    self.scroll_to_selected_quad = true;
+                }
             });
         }
 
-        if self.physics_enabled {
+        if self.physics_enabled && !self.paused_for_focus {
             ctx.request_repaint();
         }
     }
 
+    // The window size and position are restored by eframe as long as this is enabled
+    fn persist_native_window(&self) -> bool {
+        true
+    }
+
     fn save(&mut self, storage: &mut dyn eframe::Storage) {
         storage.set_string("App", serde_json::to_string(self).unwrap());
         storage.flush();
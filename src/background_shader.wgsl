@@ -0,0 +1,34 @@
+struct VertexInput {
+    @builtin(vertex_index) vertex_index: u32,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+};
+
+struct Background {
+    color: vec3<f32>,
+};
+
+@group(0)
+@binding(0)
+var<uniform> background: Background;
+
+@vertex
+fn vs_main(model: VertexInput) -> VertexOutput {
+    var output: VertexOutput;
+
+    // A single triangle that covers the whole viewport
+    let uv = vec2<f32>(
+        f32((model.vertex_index << 1u) & 2u),
+        f32(model.vertex_index & 2u),
+    );
+
+    output.clip_position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    return output;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(background.color, 1.0);
+}
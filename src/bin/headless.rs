@@ -0,0 +1,111 @@
+use std::fmt::Write;
+
+use physics::{PhysicsWorld, Scene};
+
+// Loads a scene, steps it with no window and prints where everything ended up, for benchmarking
+// and for comparing the results of the solver between changes
+struct Args {
+    scene: String,
+    steps: usize,
+    hz: f32,
+    output: Option<String>,
+}
+
+fn parse_args() -> Result<Args, String> {
+    fn value<T: std::str::FromStr>(arg: &str, value: Option<String>) -> Result<T, String> {
+        let value = value.ok_or_else(|| format!("Expected a value after {arg}"))?;
+        value
+            .parse()
+            .map_err(|_| format!("Invalid value for {arg}: '{value}'"))
+    }
+
+    let mut scene = None;
+    let mut steps = 1000;
+    let mut hz = 100.0;
+    let mut output = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--scene" => scene = Some(value(&arg, args.next())?),
+            "--steps" => steps = value(&arg, args.next())?,
+            "--hz" => hz = value(&arg, args.next())?,
+            "--output" => output = Some(value(&arg, args.next())?),
+            _ => return Err(format!("Unknown argument '{arg}'")),
+        }
+    }
+    if hz <= 0.0 {
+        return Err(format!("--hz must be positive, got {hz}"));
+    }
+    Ok(Args {
+        scene: scene.ok_or("Expected --scene PATH")?,
+        steps,
+        hz,
+        output,
+    })
+}
+
+fn main() {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(error) => {
+            eprintln!("{error}");
+            eprintln!("Usage: headless --scene PATH [--steps N] [--hz RATE] [--output PATH]");
+            std::process::exit(1);
+        }
+    };
+    let scene = match Scene::load(&args.scene) {
+        Ok(scene) => scene,
+        Err(error) => {
+            eprintln!("Failed to load '{}': {error}", args.scene);
+            std::process::exit(1);
+        }
+    };
+
+    let mut world = PhysicsWorld::new(scene.quads);
+    world.capsules = scene.capsules;
+    world.joints = scene.joints;
+    world.springs = scene.springs;
+    world.gravity = scene.gravity;
+
+    let start = std::time::Instant::now();
+    let ts = 1.0 / args.hz;
+    let mut capped_steps = 0;
+    for _ in 0..args.steps {
+        world.step(ts);
+        capped_steps += world.last_step_report().hit_iteration_cap as usize;
+    }
+    let elapsed = start.elapsed();
+
+    let mut positions = String::new();
+    for (i, quad) in world.quads.iter().enumerate() {
+        writeln!(
+            positions,
+            "quad {i}: position {} {} rotation {}",
+            quad.position.x, quad.position.y, quad.rotation
+        )
+        .unwrap();
+    }
+    for (i, capsule) in world.capsules.iter().enumerate() {
+        writeln!(
+            positions,
+            "capsule {i}: position {} {} rotation {}",
+            capsule.position.x, capsule.position.y, capsule.rotation
+        )
+        .unwrap();
+    }
+    match &args.output {
+        Some(path) => {
+            if let Err(error) = std::fs::write(path, positions) {
+                eprintln!("Failed to write '{path}': {error}");
+                std::process::exit(1);
+            }
+        }
+        None => print!("{positions}"),
+    }
+    eprintln!("{} steps took {elapsed:?}", args.steps);
+    if capped_steps > 0 {
+        eprintln!(
+            "{capped_steps} steps reached the iteration limit, the simulation may be unstable"
+        );
+    }
+}
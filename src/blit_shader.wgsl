@@ -0,0 +1,36 @@
+struct VertexInput {
+    @builtin(vertex_index) vertex_index: u32,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@group(0)
+@binding(0)
+var scene_texture: texture_2d<f32>;
+@group(0)
+@binding(1)
+var scene_sampler: sampler;
+
+@vertex
+fn vs_main(model: VertexInput) -> VertexOutput {
+    var output: VertexOutput;
+
+    // A single triangle that covers the whole viewport
+    let uv = vec2<f32>(
+        f32((model.vertex_index << 1u) & 2u),
+        f32(model.vertex_index & 2u),
+    );
+
+    output.clip_position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    // Textures have y going down
+    output.uv = vec2<f32>(uv.x, 1.0 - uv.y);
+    return output;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(scene_texture, scene_sampler, in.uv);
+}
@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+
+use crate::Aabb;
+
+// Aabbs covering more cells than this go in the overflow list instead, so a huge or infinite
+// aabb doesnt loop over billions of cells
+const MAX_CELLS: i64 = 4096;
+
+// A uniform grid that every collider is put into all the cells its aabb overlaps,
+// so finding the colliders near something only has to look at the cells around it
+#[derive(Default)]
+pub struct Broadphase {
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<usize>>,
+    // Colliders that are too big for the grid or have a nan aabb, every query includes them
+    overflow: Vec<usize>,
+    count: usize,
+}
+
+impl Broadphase {
+    pub fn build(&mut self, cell_size: f32, aabbs: impl IntoIterator<Item = Aabb>) {
+        self.cell_size = cell_size;
+        // Keep the allocations of the cells around for the next time the grid is built
+        self.cells.values_mut().for_each(Vec::clear);
+        self.overflow.clear();
+        self.count = 0;
+        for (index, aabb) in aabbs.into_iter().enumerate() {
+            self.count = index + 1;
+            let Some((min_cell, max_cell)) = self.cell_range(aabb) else {
+                self.overflow.push(index);
+                continue;
+            };
+            for x in min_cell.0..=max_cell.0 {
+                for y in min_cell.1..=max_cell.1 {
+                    self.cells.entry((x, y)).or_default().push(index);
+                }
+            }
+        }
+    }
+
+    // The indices of every collider that might overlap the aabb, sorted so they are in the same order as brute force
+    pub fn query(&self, aabb: Aabb) -> Vec<usize> {
+        let mut indices = vec![];
+        self.query_into(aabb, &mut indices);
+        indices
+    }
+
+    // The same as `query`, but reuses the allocation of `indices`
+    pub fn query_into(&self, aabb: Aabb, indices: &mut Vec<usize>) {
+        indices.clear();
+        let Some((min_cell, max_cell)) = self.cell_range(aabb) else {
+            // Anything could be inside an aabb that is too big to look up cell by cell
+            indices.extend(0..self.count);
+            return;
+        };
+        indices.extend_from_slice(&self.overflow);
+        for x in min_cell.0..=max_cell.0 {
+            for y in min_cell.1..=max_cell.1 {
+                if let Some(cell) = self.cells.get(&(x, y)) {
+                    indices.extend_from_slice(cell);
+                }
+            }
+        }
+        indices.sort_unstable();
+        indices.dedup();
+    }
+
+    // The first and last cells the aabb is in, or `None` if it isnt finite or covers too many cells
+    fn cell_range(&self, aabb: Aabb) -> Option<((i32, i32), (i32, i32))> {
+        let finite = aabb.min.x.is_finite()
+            && aabb.min.y.is_finite()
+            && aabb.max.x.is_finite()
+            && aabb.max.y.is_finite();
+        if !finite {
+            return None;
+        }
+        let (min_cell, max_cell) = (self.cell(aabb.min), self.cell(aabb.max));
+        // The casts saturate, so the widths fit in an i64
+        let width = (max_cell.0 as i64 - min_cell.0 as i64 + 1).max(0);
+        let height = (max_cell.1 as i64 - min_cell.1 as i64 + 1).max(0);
+        (width.saturating_mul(height) <= MAX_CELLS).then_some((min_cell, max_cell))
+    }
+
+    fn cell(&self, position: cgmath::Vector2<f32>) -> (i32, i32) {
+        (
+            (position.x / self.cell_size).floor() as i32,
+            (position.y / self.cell_size).floor() as i32,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng};
+
+    fn overlaps(a: &Aabb, b: &Aabb) -> bool {
+        a.min.x <= b.max.x && b.min.x <= a.max.x && a.min.y <= b.max.y && b.min.y <= a.max.y
+    }
+
+    fn random_aabb(rng: &mut impl Rng) -> Aabb {
+        let center = cgmath::vec2(rng.gen_range(-30.0..30.0), rng.gen_range(-30.0..30.0));
+        // Mostly small ones, with some that cover lots of cells
+        let max_size = if rng.gen_bool(0.1) { 40.0 } else { 3.0 };
+        let half_size = cgmath::vec2(rng.gen_range(0.0..max_size), rng.gen_range(0.0..max_size));
+        Aabb {
+            min: center - half_size,
+            max: center + half_size,
+        }
+    }
+
+    #[test]
+    fn finds_every_overlapping_pair() {
+        for seed in 0..50 {
+            let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+            let aabbs = (0..rng.gen_range(1..200))
+                .map(|_| random_aabb(&mut rng))
+                .collect::<Vec<_>>();
+            let cell_size = rng.gen_range(0.5..5.0);
+            let mut broadphase = Broadphase::default();
+            broadphase.build(cell_size, aabbs.iter().copied());
+
+            for (index, aabb) in aabbs.iter().enumerate() {
+                let found = broadphase.query(*aabb);
+                for (other_index, other) in aabbs.iter().enumerate() {
+                    if overlaps(aabb, other) {
+                        assert!(
+                            found.binary_search(&other_index).is_ok(),
+                            "seed {seed}: {index} and {other_index} overlap but werent found"
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn huge_and_nan_aabbs_go_in_the_overflow() {
+        let small = Aabb {
+            min: cgmath::vec2(0.0, 0.0),
+            max: cgmath::vec2(1.0, 1.0),
+        };
+        let huge = Aabb {
+            min: cgmath::vec2(-1e30, -1e30),
+            max: cgmath::vec2(1e30, 1e30),
+        };
+        let infinite = Aabb {
+            min: cgmath::vec2(f32::NEG_INFINITY, 0.0),
+            max: cgmath::vec2(f32::INFINITY, 1.0),
+        };
+        let nan = Aabb {
+            min: cgmath::vec2(f32::NAN, 0.0),
+            max: cgmath::vec2(1.0, f32::NAN),
+        };
+        let mut broadphase = Broadphase::default();
+        broadphase.build(1.0, [small, huge, infinite, nan]);
+
+        assert_eq!(broadphase.query(small), vec![0, 1, 2, 3]);
+        let far_away = Aabb {
+            min: cgmath::vec2(100.0, 100.0),
+            max: cgmath::vec2(101.0, 101.0),
+        };
+        assert_eq!(broadphase.query(far_away), vec![1, 2, 3]);
+        assert_eq!(broadphase.query(huge), vec![0, 1, 2, 3]);
+        assert_eq!(broadphase.query(nan), vec![0, 1, 2, 3]);
+    }
+}
@@ -0,0 +1,135 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{Aabb, CAMERA_FRAME_MARGIN};
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Camera {
+    pub position: cgmath::Vector2<f32>,
+    pub rotation: f32,
+    pub zoom: f32,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self {
+            position: cgmath::vec2(0.0, 0.0),
+            rotation: 0.0,
+            zoom: 0.25,
+        }
+    }
+}
+
+impl Camera {
+    // Centers the camera on the aabb and zooms so all of it fits in a view with this aspect ratio
+    pub fn frame(&mut self, aabb: Aabb, aspect: f32) {
+        self.position = (aabb.min + aabb.max) * 0.5;
+        // The size of the aabb once the camera rotation lines it up with the screen
+        let half_size = (aabb.max - aabb.min) * 0.5;
+        let (sin, cos) = self.rotation.sin_cos();
+        let half_width = half_size.x * cos.abs() + half_size.y * sin.abs();
+        let half_height = half_size.y * cos.abs() + half_size.x * sin.abs();
+        // `world_to_screen` puts a point at the edge of the screen when `offset.x * zoom / aspect` or `offset.y * zoom` is 1
+        let zoom = (aspect / half_width).min(1.0 / half_height) / CAMERA_FRAME_MARGIN;
+        // A single point has no size to fit, so leave the zoom alone
+        if zoom.is_finite() {
+            self.zoom = zoom;
+        }
+    }
+}
+
+// Converting to and from the screen needs egui, so it is only there with the window
+#[cfg(feature = "gui")]
+impl Camera {
+    // The same transform as `vs_main` in the quad shader, followed by the viewport transform
+    pub fn world_to_screen(
+        &self,
+        world: cgmath::Vector2<f32>,
+        rect: eframe::egui::Rect,
+    ) -> eframe::egui::Pos2 {
+        let aspect = rect.width() / rect.height();
+        let zoomed = (world - self.position) * self.zoom;
+        let rotated = cgmath::vec2(
+            zoomed.x * self.rotation.cos() - zoomed.y * self.rotation.sin(),
+            zoomed.y * self.rotation.cos() + zoomed.x * self.rotation.sin(),
+        );
+        rect.center() + eframe::egui::vec2(rotated.x / aspect, -rotated.y) * rect.size() * 0.5
+    }
+
+    pub fn screen_to_world(
+        &self,
+        screen: eframe::egui::Pos2,
+        rect: eframe::egui::Rect,
+    ) -> cgmath::Vector2<f32> {
+        let aspect = rect.width() / rect.height();
+        let clip = (screen - rect.center()) / (rect.size() * 0.5);
+        let rotated = cgmath::vec2(clip.x * aspect, -clip.y);
+        let zoomed = cgmath::vec2(
+            rotated.x * self.rotation.cos() + rotated.y * self.rotation.sin(),
+            rotated.y * self.rotation.cos() - rotated.x * self.rotation.sin(),
+        );
+        zoomed / self.zoom + self.position
+    }
+
+    // The part of the world that can be seen in `rect`, bigger than the view itself when the camera is rotated
+    pub fn visible_aabb(&self, rect: eframe::egui::Rect) -> Aabb {
+        let corners = [
+            rect.left_top(),
+            rect.right_top(),
+            rect.left_bottom(),
+            rect.right_bottom(),
+        ]
+        .map(|corner| self.screen_to_world(corner, rect));
+        corners[1..].iter().fold(
+            Aabb {
+                min: corners[0],
+                max: corners[0],
+            },
+            |aabb, &corner| {
+                aabb.union(&Aabb {
+                    min: corner,
+                    max: corner,
+                })
+            },
+        )
+    }
+}
+
+#[cfg(all(test, feature = "gui"))]
+mod tests {
+    use super::*;
+    use cgmath::prelude::*;
+    use eframe::egui;
+
+    #[test]
+    fn world_to_screen_round_trips_within_a_pixel() {
+        let rect = egui::Rect::from_min_size(egui::pos2(30.0, 20.0), egui::vec2(1280.0, 720.0));
+        for (position, rotation, zoom) in [
+            (cgmath::vec2(0.0, 0.0), 0.0, 0.25),
+            (cgmath::vec2(12.5, -3.0), 0.7, 0.05),
+            (cgmath::vec2(-1000.0, 250.0), -2.5, 1.5),
+        ] {
+            let camera = Camera {
+                position,
+                rotation,
+                zoom,
+            };
+            // How many pixels one unit in the world covers, the same in both directions
+            let pixels_per_unit = zoom * rect.height() * 0.5;
+            for x in [-1.0f32, -0.3, 0.0, 0.5, 1.0] {
+                for y in [-1.0f32, 0.2, 1.0] {
+                    let world = position + cgmath::vec2(x * rect.width() / rect.height(), y) / zoom;
+                    let screen = camera.world_to_screen(world, rect);
+                    let back = camera.screen_to_world(screen, rect);
+                    let error = back.distance(world) * pixels_per_unit;
+                    assert!(
+                        error < 1.0,
+                        "{world:?} came back as {back:?}, {error} pixels off"
+                    );
+
+                    let screen_back = camera.world_to_screen(back, rect);
+                    assert!(screen_back.distance(screen) < 1.0);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use cgmath::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{default_layer, default_mask, deserialize_body_type, BodyType, Collider, Quad};
+
+fn default_gravity_scale() -> f32 {
+    1.0
+}
+
+// A line segment along the local x axis with rounded ends, `half_length` doesnt include the ends
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Capsule {
+    pub position: cgmath::Vector2<f32>,
+    pub velocity: cgmath::Vector2<f32>,
+    pub rotation: f32,
+    pub angular_velocity: f32,
+    pub half_length: f32,
+    pub radius: f32,
+    pub color: cgmath::Vector3<f32>,
+    #[serde(default, alias = "dynamic", deserialize_with = "deserialize_body_type")]
+    pub body_type: BodyType,
+    pub mass: f32,
+    // These work the same as they do for quads
+    #[serde(default = "default_gravity_scale")]
+    pub gravity_scale: f32,
+    pub linear_damping: Option<f32>,
+    pub angular_damping: Option<f32>,
+    #[serde(default = "default_layer")]
+    pub layer: u32,
+    #[serde(default = "default_mask")]
+    pub mask: u32,
+}
+
+impl Capsule {
+    // Static and kinematic capsules have infinite mass
+    pub fn inverse_mass(&self) -> f32 {
+        if self.body_type == BodyType::Dynamic {
+            1.0 / self.mass
+        } else {
+            0.0
+        }
+    }
+
+    // The ends of the segment, rotated the same way as quads
+    pub fn endpoints(&self) -> [cgmath::Vector2<f32>; 2] {
+        let axis = cgmath::vec2((-self.rotation).cos(), (-self.rotation).sin()) * self.half_length;
+        [self.position - axis, self.position + axis]
+    }
+
+    // Only one of them has to want to collide with the other one, the same as for quads
+    pub fn collides_with(&self, other: &Capsule) -> bool {
+        self.layer & other.mask != 0 || other.layer & self.mask != 0
+    }
+
+    pub fn collides_with_quad(&self, quad: &Quad) -> bool {
+        self.layer & quad.mask != 0 || quad.layer & self.mask != 0
+    }
+}
+
+impl Collider for Capsule {
+    fn center(&self) -> cgmath::Vector2<f32> {
+        self.position
+    }
+
+    fn furthest_point_in_direction(&self, direction: cgmath::Vector2<f32>) -> cgmath::Vector2<f32> {
+        // There is no furthest point without a direction, and normalizing it would give nan
+        let length = direction.magnitude();
+        if length == 0.0 {
+            return self.position;
+        }
+        let [a, b] = self.endpoints();
+        let point = if a.dot(direction) > b.dot(direction) {
+            a
+        } else {
+            b
+        };
+        point + direction / length * self.radius
+    }
+}
+
+impl Default for Capsule {
+    fn default() -> Self {
+        Self {
+            position: cgmath::vec2(0.0, 0.0),
+            velocity: cgmath::vec2(0.0, 0.0),
+            rotation: 0.0,
+            angular_velocity: 0.0,
+            half_length: 0.5,
+            radius: 0.25,
+            color: cgmath::vec3(1.0, 1.0, 1.0),
+            body_type: BodyType::Dynamic,
+            mass: 1.0,
+            gravity_scale: 1.0,
+            linear_damping: None,
+            angular_damping: None,
+            layer: 1,
+            mask: u32::MAX,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zero_direction_gives_the_center() {
+        let capsule = Capsule {
+            position: cgmath::vec2(1.0, 2.0),
+            ..Capsule::default()
+        };
+        assert_eq!(
+            capsule.furthest_point_in_direction(cgmath::vec2(0.0, 0.0)),
+            capsule.position
+        );
+        let point = capsule.furthest_point_in_direction(cgmath::vec2(1e-30, 0.0));
+        assert!(point.x.is_finite() && point.y.is_finite());
+    }
+}
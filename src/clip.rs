@@ -0,0 +1,139 @@
+use arrayvec::ArrayVec;
+use cgmath::prelude::*;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Face {
+    pub a: cgmath::Vector2<f32>,
+    pub b: cgmath::Vector2<f32>,
+    // Points out of the shape the face belongs to
+    pub normal: cgmath::Vector2<f32>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ContactPoint {
+    pub position: cgmath::Vector2<f32>,
+    // How far behind the reference face the point is
+    pub depth: f32,
+}
+
+// Clips the incident face to the sides of the reference face, and keeps the points that are behind it.
+// A reference face where `a == b` does not clip anything to its sides, so round shapes can use a single point
+pub fn clip_faces(reference_face: Face, incident_face: Face) -> ArrayVec<ContactPoint, 2> {
+    let tangent = reference_face.b - reference_face.a;
+    let points = [incident_face.a, incident_face.b];
+    let Some(points) = clip_segment(points, tangent, tangent.dot(reference_face.a)) else { return ArrayVec::new(); };
+    let Some(points) = clip_segment(points, -tangent, -tangent.dot(reference_face.b)) else { return ArrayVec::new(); };
+
+    let reference_distance = reference_face.normal.dot(reference_face.a);
+    points
+        .into_iter()
+        .map(|position| ContactPoint {
+            position,
+            depth: reference_distance - reference_face.normal.dot(position),
+        })
+        .filter(|point| point.depth >= 0.0)
+        .collect()
+}
+
+// Keeps the part of the segment where `direction.dot(point) >= offset`
+fn clip_segment(
+    [a, b]: [cgmath::Vector2<f32>; 2],
+    direction: cgmath::Vector2<f32>,
+    offset: f32,
+) -> Option<[cgmath::Vector2<f32>; 2]> {
+    let distance_a = direction.dot(a) - offset;
+    let distance_b = direction.dot(b) - offset;
+    if distance_a >= 0.0 && distance_b >= 0.0 {
+        Some([a, b])
+    } else if distance_a < 0.0 && distance_b < 0.0 {
+        None
+    } else {
+        let intersection = a + (b - a) * (distance_a / (distance_a - distance_b));
+        if distance_a < 0.0 {
+            Some([intersection, b])
+        } else {
+            Some([a, intersection])
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_point(point: ContactPoint, position: cgmath::Vector2<f32>, depth: f32) {
+        assert!(
+            (point.position - position).magnitude() < 1e-5 && (point.depth - depth).abs() < 1e-5,
+            "{point:?} != {position:?} at depth {depth}"
+        );
+    }
+
+    // The top face of a box from -1 to 1
+    fn floor() -> Face {
+        Face {
+            a: cgmath::vec2(-1.0, 0.0),
+            b: cgmath::vec2(1.0, 0.0),
+            normal: cgmath::vec2(0.0, 1.0),
+        }
+    }
+
+    #[test]
+    fn overlapping_face_is_clipped_to_the_reference_sides() {
+        let incident = Face {
+            a: cgmath::vec2(0.5, -0.1),
+            b: cgmath::vec2(2.5, -0.1),
+            normal: cgmath::vec2(0.0, -1.0),
+        };
+        let points = clip_faces(floor(), incident);
+        assert_eq!(points.len(), 2);
+        assert_point(points[0], cgmath::vec2(0.5, -0.1), 0.1);
+        assert_point(points[1], cgmath::vec2(1.0, -0.1), 0.1);
+    }
+
+    #[test]
+    fn tilted_face_only_keeps_points_behind_the_reference() {
+        let incident = Face {
+            a: cgmath::vec2(-0.5, 0.25),
+            b: cgmath::vec2(0.5, -0.25),
+            normal: cgmath::vec2(-0.5, -1.0).normalize(),
+        };
+        let points = clip_faces(floor(), incident);
+        assert_eq!(points.len(), 1);
+        assert_point(points[0], cgmath::vec2(0.5, -0.25), 0.25);
+    }
+
+    #[test]
+    fn faces_that_dont_overlap_give_no_points() {
+        let incident = Face {
+            a: cgmath::vec2(1.5, -0.1),
+            b: cgmath::vec2(3.0, -0.1),
+            normal: cgmath::vec2(0.0, -1.0),
+        };
+        assert!(clip_faces(floor(), incident).is_empty());
+
+        let above = Face {
+            a: cgmath::vec2(-0.5, 0.1),
+            b: cgmath::vec2(0.5, 0.1),
+            normal: cgmath::vec2(0.0, -1.0),
+        };
+        assert!(clip_faces(floor(), above).is_empty());
+    }
+
+    #[test]
+    fn point_reference_face_doesnt_clip_sides() {
+        let point = Face {
+            a: cgmath::vec2(0.0, 0.0),
+            b: cgmath::vec2(0.0, 0.0),
+            normal: cgmath::vec2(0.0, 1.0),
+        };
+        let incident = Face {
+            a: cgmath::vec2(-3.0, -0.5),
+            b: cgmath::vec2(3.0, -0.5),
+            normal: cgmath::vec2(0.0, -1.0),
+        };
+        let points = clip_faces(point, incident);
+        assert_eq!(points.len(), 2);
+        assert_point(points[0], cgmath::vec2(-3.0, -0.5), 0.5);
+        assert_point(points[1], cgmath::vec2(3.0, -0.5), 0.5);
+    }
+}
@@ -1,24 +1,512 @@
 use arrayvec::ArrayVec;
 use cgmath::prelude::*;
+use serde::{Deserialize, Serialize};
 
-use crate::MAX_PHYSICS_ITERATIONS;
+use crate::{
+    clip_faces, Face, SweepingCollider, GJK_TOLERANCE, MAX_PHYSICS_ITERATIONS, PENETRATION_SLOP,
+};
 
 pub trait Collider {
     fn center(&self) -> cgmath::Vector2<f32>;
     fn furthest_point_in_direction(&self, direction: cgmath::Vector2<f32>) -> cgmath::Vector2<f32>;
+
+    fn aabb(&self) -> Aabb {
+        Aabb {
+            min: cgmath::vec2(
+                self.furthest_point_in_direction(cgmath::vec2(-1.0, 0.0)).x,
+                self.furthest_point_in_direction(cgmath::vec2(0.0, -1.0)).y,
+            ),
+            max: cgmath::vec2(
+                self.furthest_point_in_direction(cgmath::vec2(1.0, 0.0)).x,
+                self.furthest_point_in_direction(cgmath::vec2(0.0, 1.0)).y,
+            ),
+        }
+    }
+
+    // The face that points the most along `direction`, shapes without flat faces can use a single point
+    fn face_in_direction(&self, direction: cgmath::Vector2<f32>) -> Face {
+        let point = self.furthest_point_in_direction(direction);
+        Face {
+            a: point,
+            b: point,
+            normal: direction.normalize(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: cgmath::Vector2<f32>,
+    pub max: cgmath::Vector2<f32>,
+}
+
+impl Aabb {
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        self.min.x <= other.max.x
+            && self.max.x >= other.min.x
+            && self.min.y <= other.max.y
+            && self.max.y >= other.min.y
+    }
+
+    pub fn contains_point(&self, point: cgmath::Vector2<f32>) -> bool {
+        point.x >= self.min.x
+            && point.x <= self.max.x
+            && point.y >= self.min.y
+            && point.y <= self.max.y
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: cgmath::vec2(self.min.x.min(other.min.x), self.min.y.min(other.min.y)),
+            max: cgmath::vec2(self.max.x.max(other.max.x), self.max.y.max(other.max.y)),
+        }
+    }
 }
 
+#[derive(Debug, Clone)]
 pub struct Collision {
     pub normal: cgmath::Vector2<f32>,
     pub depth: f32,
+    // In world space, on the surface of the shape that was clipped against the other's face
+    pub contact_points: ArrayVec<cgmath::Vector2<f32>, 2>,
+}
+
+impl Collision {
+    // Makes the normal point along `direction`, returns whether the normal had to be flipped
+    pub fn orient_towards(&mut self, direction: cgmath::Vector2<f32>) -> bool {
+        if self.normal.dot(direction) < 0.0 {
+            self.normal = -self.normal;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn rotate_normal(&mut self, angle: f32) {
+        let (sin, cos) = angle.sin_cos();
+        self.normal = cgmath::vec2(
+            self.normal.x * cos - self.normal.y * sin,
+            self.normal.y * cos + self.normal.x * sin,
+        );
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct CollisionParams {
+    // How close EPA has to get to the edge of the minkowski difference, also added to the depth so quads end up just apart
+    pub epa_tolerance: f32,
+    pub max_iterations: usize,
+    // How much of an overlap deeper than `penetration_slop` is pushed out each step, the rest is left for later steps
+    // so quads that start out deep inside each other settle instead of popping apart
+    pub position_correction_factor: f32,
+    // Overlaps this shallow are pushed out in one step like any resting contact, leaving them overlapped makes the
+    // solver fight over them every iteration
+    pub penetration_slop: f32,
+}
+
+impl Default for CollisionParams {
+    fn default() -> Self {
+        Self {
+            epa_tolerance: 0.001,
+            max_iterations: MAX_PHYSICS_ITERATIONS,
+            position_correction_factor: 0.2,
+            penetration_slop: PENETRATION_SLOP,
+        }
+    }
+}
+
+// `cap_hits` is increased every time GJK or EPA gives up after `max_iterations`, so the caller can count how often
+// it happens. The collision functions below that can run out of iterations all take it too
+pub fn get_collision<C1, C2>(
+    c1: &C1,
+    c2: &C2,
+    params: &CollisionParams,
+    cap_hits: &mut usize,
+) -> Option<Collision>
+where
+    C1: Collider + ?Sized,
+    C2: Collider + ?Sized,
+{
+    gjk(c1, c2, params, cap_hits).and_then(|simplex| epa(simplex.into(), c1, c2, params, cap_hits))
+}
+
+// A contact between colliders that are apart but less than `max_distance` from each other, with the gap as a
+// negative depth. The solver lets them close the gap in one step and stops them at contact, so fast colliders
+// cant skip over each other without sweeping them
+pub fn get_speculative_collision<C1, C2>(
+    c1: &C1,
+    c2: &C2,
+    max_distance: f32,
+    params: &CollisionParams,
+    cap_hits: &mut usize,
+) -> Option<Collision>
+where
+    C1: Collider + ?Sized,
+    C2: Collider + ?Sized,
+{
+    let (distance, closest_1, closest_2) = distance_between(c1, c2, params, cap_hits)?;
+    if distance <= 0.0 || distance > max_distance {
+        return None;
+    }
+    let normal = (closest_2 - closest_1) / distance;
+    Some(Collision {
+        normal,
+        depth: -distance,
+        contact_points: contact_points(c1, c2, normal, distance + PENETRATION_SLOP),
+    })
+}
+
+// How far the collider reaches from its center, only checked along a few directions so its exact for quads, which
+// have every corner the same distance away
+fn bounding_radius<C: Collider + ?Sized>(collider: &C) -> f32 {
+    (0..8)
+        .map(|i| {
+            let (sin, cos) = (i as f32 * std::f32::consts::FRAC_PI_4).sin_cos();
+            let direction = cgmath::vec2(cos, sin);
+            collider
+                .furthest_point_in_direction(direction)
+                .distance(collider.center())
+        })
+        .fold(0.0, f32::max)
+}
+
+// Finds when the colliders first touch during their sweeps, by conservative advancement. The collision is for where
+// they are now, with how far they can still get closer along the normal as a negative depth like a speculative
+// contact, so the solver stops them at the surface instead of pushing them out the far side after they have passed
+// through each other. `None` if they dont touch during the sweeps or already overlap
+pub fn get_swept_collision<C1, C2>(
+    sweep_1: &SweepingCollider<'_, C1>,
+    sweep_2: &SweepingCollider<'_, C2>,
+    params: &CollisionParams,
+    cap_hits: &mut usize,
+) -> Option<Collision>
+where
+    C1: Collider + ?Sized,
+    C2: Collider + ?Sized,
+{
+    let moved = |time: f32| (sweep_1.at(time), sweep_2.at(time));
+    let movement =
+        (sweep_1.position_b - sweep_1.position_a) - (sweep_2.position_b - sweep_2.position_a);
+    // Turning can bring them closer by at most how far their furthest points swing
+    let spin = (sweep_1.rotation_b - sweep_1.rotation_a).abs() * bounding_radius(sweep_1.collider)
+        + (sweep_2.rotation_b - sweep_2.rotation_a).abs() * bounding_radius(sweep_2.collider);
+
+    let mut time = 0.0;
+    let mut normal = None;
+    for _ in 0..params.max_iterations {
+        let (moved_1, moved_2) = moved(time);
+        let Some((distance, closest_1, closest_2)) =
+            distance_between(&moved_1, &moved_2, params, cap_hits)
+        else {
+            break;
+        };
+        if distance <= 0.0 {
+            break;
+        }
+        let current_normal = (closest_2 - closest_1) / distance;
+        normal = Some(current_normal);
+        // Close enough for the contact points to be found, when spinning each step gets them less of the way there
+        if distance <= PENETRATION_SLOP {
+            break;
+        }
+
+        // Moving by the distance along the normal can never take them past each other
+        let closing_speed = movement.dot(current_normal) + spin;
+        if closing_speed <= 0.0 {
+            return None;
+        }
+        time += distance / closing_speed;
+        if time > 1.0 {
+            return None;
+        }
+    }
+
+    let normal = match normal {
+        Some(normal) => normal,
+        // Already touching, so use the face they are touching on
+        None if time == 0.0
+            && distance_between(&sweep_1.at(0.0), &sweep_2.at(0.0), params, cap_hits).is_some() =>
+        {
+            -sweep_2.at(0.0).face_in_direction(-movement).normal
+        }
+        None => return None,
+    };
+    let (moved_1, moved_2) = moved(time);
+    // Only moved back by how far the first collider moves and not turned back, so the solver sees the points moving
+    // the way they are when they touch, even for quads spinning most of a turn each step
+    let contact_points: ArrayVec<_, 2> =
+        contact_points(&moved_1, &moved_2, normal, PENETRATION_SLOP)
+            .into_iter()
+            .map(|point| point - (sweep_1.position_b - sweep_1.position_a) * time)
+            .collect();
+    // How far the touching points get closer along the normal over the whole sweep
+    let closing = contact_points
+        .iter()
+        .map(|&point| {
+            let point = point + (sweep_1.position_b - sweep_1.position_a) * time;
+            (sweep_1.movement_at(point, time) - sweep_2.movement_at(point, time)).dot(normal)
+        })
+        .fold(f32::INFINITY, f32::min);
+    // If they would only just overlap by the end, like quads resting on each other, the normal contacts can deal
+    // with it. Otherwise resting quads fight over the tiny gaps every iteration and stacks fall apart
+    if contact_points.is_empty() || closing * (1.0 - time) <= PENETRATION_SLOP {
+        return None;
+    }
+    Some(Collision {
+        normal,
+        depth: -closing * time,
+        contact_points,
+    })
+}
+
+pub struct RayHit {
+    pub collider_index: usize,
+    pub point: cgmath::Vector2<f32>,
+    pub normal: cgmath::Vector2<f32>,
+    pub distance: f32,
+}
+
+// Finds the closest collider that the ray hits within `max_distance`, rays that start inside a collider hit it at distance 0
+pub fn raycast<C>(
+    colliders: &[C],
+    origin: cgmath::Vector2<f32>,
+    direction: cgmath::Vector2<f32>,
+    max_distance: f32,
+    params: &CollisionParams,
+) -> Option<RayHit>
+where
+    C: Collider,
+{
+    let direction = direction.normalize();
+    colliders
+        .iter()
+        .enumerate()
+        .filter_map(|(collider_index, collider)| {
+            let (distance, normal) =
+                raycast_collider(collider, origin, direction, max_distance, params)?;
+            Some(RayHit {
+                collider_index,
+                point: origin + direction * distance,
+                normal,
+                distance,
+            })
+        })
+        .min_by(|a, b| a.distance.total_cmp(&b.distance))
+}
+
+// The GJK ray cast from "Ray Casting against General Convex Objects with Application to Continuous Collision Detection",
+// it moves the start of the ray forward until it touches the collider, using the support function to never move past it
+fn raycast_collider<C>(
+    collider: &C,
+    origin: cgmath::Vector2<f32>,
+    direction: cgmath::Vector2<f32>,
+    max_distance: f32,
+    params: &CollisionParams,
+) -> Option<(f32, cgmath::Vector2<f32>)>
+where
+    C: Collider + ?Sized,
+{
+    let mut distance = 0.0;
+    let mut point = origin;
+    let mut normal = cgmath::vec2(0.0, 0.0);
+    let mut simplex = ArrayVec::<cgmath::Vector2<f32>, 3>::new();
+    let mut closest = origin - collider.center();
+    for _ in 0..params.max_iterations {
+        if closest.magnitude2() <= GJK_TOLERANCE * GJK_TOLERANCE {
+            // The ray started inside the collider, so there is no face it went through
+            if normal.is_zero() {
+                normal = -direction;
+            }
+            return Some((distance, normal.normalize()));
+        }
+
+        let support = collider.furthest_point_in_direction(closest);
+        let w = point - support;
+        if closest.dot(w) > 0.0 {
+            // The collider is entirely on the other side of the plane through the support point
+            if closest.dot(direction) >= 0.0 {
+                return None;
+            }
+            distance -= closest.dot(w) / closest.dot(direction);
+            if distance > max_distance {
+                return None;
+            }
+            point = origin + direction * distance;
+            normal = closest;
+        }
+
+        if !simplex.contains(&support) {
+            simplex.push(support);
+        }
+        let points: ArrayVec<_, 3> = simplex.iter().map(|&support| point - support).collect();
+        let weights = closest_point_weights(&points);
+        closest = weights
+            .iter()
+            .map(|&(index, weight)| points[index] * weight)
+            .sum();
+        simplex = weights.iter().map(|&(index, _)| simplex[index]).collect();
+    }
+
+    // Assume it missed
+    None
 }
 
-pub fn get_collision<C1, C2>(c1: &C1, c2: &C2) -> Option<Collision>
+// Returns the distance between the colliders and the closest point on each of them, or `None` if they are overlapping.
+// Colliders that are exactly touching have a distance of 0
+pub fn distance_between<C1, C2>(
+    c1: &C1,
+    c2: &C2,
+    params: &CollisionParams,
+    cap_hits: &mut usize,
+) -> Option<(f32, cgmath::Vector2<f32>, cgmath::Vector2<f32>)>
 where
     C1: Collider + ?Sized,
     C2: Collider + ?Sized,
 {
-    gjk(c1, c2).and_then(|simplex| epa(simplex.into(), c1, c2))
+    // The points on both colliders are kept so the closest points can be found from the closest point on the simplex
+    let mut simplex = ArrayVec::<(cgmath::Vector2<f32>, cgmath::Vector2<f32>), 3>::new();
+    let d = c2.center() - c1.center();
+    simplex.push((
+        c1.furthest_point_in_direction(d),
+        c2.furthest_point_in_direction(-d),
+    ));
+    for _ in 0..params.max_iterations {
+        let points: ArrayVec<_, 3> = simplex.iter().map(|&(a, b)| a - b).collect();
+        let weights = closest_point_weights(&points);
+        if weights.len() == 3 {
+            // The origin is strictly inside the simplex, so the colliders overlap
+            return None;
+        }
+        let closest_a = weights
+            .iter()
+            .map(|&(index, weight)| simplex[index].0 * weight)
+            .sum::<cgmath::Vector2<f32>>();
+        let closest_b = weights
+            .iter()
+            .map(|&(index, weight)| simplex[index].1 * weight)
+            .sum::<cgmath::Vector2<f32>>();
+        let closest = closest_a - closest_b;
+        if closest.magnitude2() <= GJK_TOLERANCE * GJK_TOLERANCE {
+            return Some((0.0, closest_a, closest_b));
+        }
+
+        let a = c1.furthest_point_in_direction(-closest);
+        let b = c2.furthest_point_in_direction(closest);
+        // Stop once the new support point does not get any closer to the origin
+        if closest.magnitude2() - closest.dot(a - b) <= GJK_TOLERANCE * closest.magnitude() {
+            return Some((closest.magnitude(), closest_a, closest_b));
+        }
+        // Checked before the unneeded points are dropped, with faces that are almost touching rounding can make a
+        // point that was just dropped look closer again, and it would keep being added and dropped forever
+        if simplex.contains(&(a, b)) {
+            return Some((closest.magnitude(), closest_a, closest_b));
+        }
+        simplex = weights.iter().map(|&(index, _)| simplex[index]).collect();
+        simplex.push((a, b));
+    }
+
+    *cap_hits += 1;
+    None
+}
+
+// The barycentric weights of the point on the simplex that is closest to the origin, leaving out the points that are not needed.
+// All 3 points are only returned when the origin is strictly inside the triangle
+fn closest_point_weights(points: &[cgmath::Vector2<f32>]) -> ArrayVec<(usize, f32), 3> {
+    fn segment(points: &[cgmath::Vector2<f32>], i: usize, j: usize) -> ArrayVec<(usize, f32), 3> {
+        let a = points[i];
+        let ab = points[j] - a;
+        let t = if ab.is_zero() {
+            0.0
+        } else {
+            (-a.dot(ab) / ab.magnitude2()).clamp(0.0, 1.0)
+        };
+        if t <= 0.0 {
+            [(i, 1.0)].into_iter().collect()
+        } else if t >= 1.0 {
+            [(j, 1.0)].into_iter().collect()
+        } else {
+            [(i, 1.0 - t), (j, t)].into_iter().collect()
+        }
+    }
+
+    let cross = |a: cgmath::Vector2<f32>, b: cgmath::Vector2<f32>| a.x * b.y - a.y * b.x;
+    match *points {
+        [_] => [(0, 1.0)].into_iter().collect(),
+        [_, _] => segment(points, 0, 1),
+        [a, b, c] => {
+            let area = cross(b - a, c - a);
+            let weights = [cross(b, c) / area, cross(c, a) / area, cross(a, b) / area];
+            if area != 0.0 && weights.iter().all(|&weight| weight > 0.0) {
+                weights.into_iter().enumerate().collect()
+            } else {
+                [
+                    segment(points, 0, 1),
+                    segment(points, 1, 2),
+                    segment(points, 2, 0),
+                ]
+                .into_iter()
+                .min_by(|a, b| {
+                    let distance = |weights: &ArrayVec<(usize, f32), 3>| {
+                        weights
+                            .iter()
+                            .map(|&(index, weight)| points[index] * weight)
+                            .sum::<cgmath::Vector2<f32>>()
+                            .magnitude2()
+                    };
+                    distance(a).total_cmp(&distance(b))
+                })
+                .unwrap()
+            }
+        }
+        _ => unreachable!(),
+    }
+}
+
+// Points up to `margin` in front of the reference face count as touching it
+fn contact_points<C1, C2>(
+    c1: &C1,
+    c2: &C2,
+    normal: cgmath::Vector2<f32>,
+    margin: f32,
+) -> ArrayVec<cgmath::Vector2<f32>, 2>
+where
+    C1: Collider + ?Sized,
+    C2: Collider + ?Sized,
+{
+    // The reference face is the one most perpendicular to the normal, a face that is
+    // just a single point can only be the reference face if both of them are
+    let face_1 = c1.face_in_direction(normal);
+    let face_2 = c2.face_in_direction(-normal);
+    let alignment = |face: &Face| {
+        let edge = face.b - face.a;
+        if edge.is_zero() {
+            f32::INFINITY
+        } else {
+            edge.normalize().dot(normal).abs()
+        }
+    };
+    let (reference_face, incident_face) = if alignment(&face_1) <= alignment(&face_2) {
+        (face_1, face_2)
+    } else {
+        (face_2, face_1)
+    };
+    // Points just in front of the reference face are still touching it, otherwise a quad resting
+    // on a slightly tilted face only gets a single contact point and rocks back and forth
+    let reference_face = Face {
+        a: reference_face.a + reference_face.normal * margin,
+        b: reference_face.b + reference_face.normal * margin,
+        normal: reference_face.normal,
+    };
+
+    let mut contact_points: ArrayVec<_, 2> = clip_faces(reference_face, incident_face)
+        .into_iter()
+        .map(|point| point.position)
+        .collect();
+    if contact_points.is_empty() {
+        // Clipping can miss everything when the shapes are barely touching, so fall back to the deepest point
+        contact_points.push(c2.furthest_point_in_direction(-normal));
+    }
+    contact_points
 }
 
 fn support<C1, C2>(c1: &C1, c2: &C2, d: cgmath::Vector2<f32>) -> cgmath::Vector2<f32>
@@ -29,11 +517,35 @@ where
     c1.furthest_point_in_direction(d) - c2.furthest_point_in_direction(-d)
 }
 
-fn gjk<C1, C2>(c1: &C1, c2: &C2) -> Option<[cgmath::Vector2<f32>; 3]>
+fn gjk<C1, C2>(
+    c1: &C1,
+    c2: &C2,
+    params: &CollisionParams,
+    cap_hits: &mut usize,
+) -> Option<[cgmath::Vector2<f32>; 3]>
 where
     C1: Collider + ?Sized,
     C2: Collider + ?Sized,
 {
+    // The direction perpendicular to `edge` on the side of `towards`. When `towards` is almost in line with the edge,
+    // like when the origin is right on it, that side cant be trusted, so either perpendicular is used to keep searching
+    fn perpendicular(
+        edge: cgmath::Vector2<f32>,
+        towards: cgmath::Vector2<f32>,
+    ) -> cgmath::Vector2<f32> {
+        let perp = {
+            let edge = cgmath::vec3(edge.x, edge.y, 0.0);
+            let towards = cgmath::vec3(towards.x, towards.y, 0.0);
+            edge.cross(towards).cross(edge).xy()
+        };
+        // The perpendicular is scaled by the squared length of the edge
+        if perp.magnitude2() <= (GJK_TOLERANCE * edge.magnitude2()).powi(2) {
+            cgmath::vec2(-edge.y, edge.x)
+        } else {
+            perp
+        }
+    }
+
     fn handle_simplex(
         simplex: &mut ArrayVec<cgmath::Vector2<f32>, 3>,
         d: &mut cgmath::Vector2<f32>,
@@ -45,12 +557,7 @@ where
             let &[b, a] = simplex.as_slice() else { unreachable!() };
             let ab = b - a;
             let ao = -a;
-            let ab_perp = {
-                let ab = cgmath::vec3(ab.x, ab.y, 0.0);
-                let ao = cgmath::vec3(ao.x, ao.y, 0.0);
-                ab.cross(ao).cross(ab).xy()
-            };
-            *d = ab_perp;
+            *d = perpendicular(ab, ao);
             false
         }
 
@@ -62,16 +569,8 @@ where
             let ab = b - a;
             let ac = c - a;
             let ao = -a;
-            let ab_perp = {
-                let ac = cgmath::vec3(ac.x, ac.y, 0.0);
-                let ab = cgmath::vec3(ab.x, ab.y, 0.0);
-                ac.cross(ab).cross(ab).xy()
-            };
-            let ac_perp = {
-                let ab = cgmath::vec3(ab.x, ab.y, 0.0);
-                let ac = cgmath::vec3(ac.x, ac.y, 0.0);
-                ab.cross(ac).cross(ac).xy()
-            };
+            let ab_perp = perpendicular(ab, -ac);
+            let ac_perp = perpendicular(ac, -ab);
             if cgmath::dot(ab_perp, ao) > 0.0 {
                 simplex.remove(0);
                 *d = ab_perp;
@@ -92,11 +591,15 @@ where
         }
     }
 
-    let mut d = (c2.center() - c1.center()).normalize();
+    // Colliders that share a center, like quads spawned on top of each other, have no direction between them
+    let mut d = c2.center() - c1.center();
+    if d.magnitude2() <= GJK_TOLERANCE * GJK_TOLERANCE {
+        d = cgmath::vec2(1.0, 0.0);
+    }
     let mut simplex = ArrayVec::new();
     simplex.push(support(c1, c2, d));
     d = -simplex[0];
-    loop {
+    for _ in 0..params.max_iterations {
         let a = support(c1, c2, d);
         if cgmath::dot(a, d) < 0.0 {
             return None;
@@ -106,9 +609,19 @@ where
             return Some(simplex.into_inner().unwrap());
         }
     }
+
+    // Assume there is no collision
+    *cap_hits += 1;
+    None
 }
 
-fn epa<C1, C2>(mut polytype: Vec<cgmath::Vector2<f32>>, c1: &C1, c2: &C2) -> Option<Collision>
+fn epa<C1, C2>(
+    mut polytype: Vec<cgmath::Vector2<f32>>,
+    c1: &C1,
+    c2: &C2,
+    params: &CollisionParams,
+    cap_hits: &mut usize,
+) -> Option<Collision>
 where
     C1: Collider + ?Sized,
     C2: Collider + ?Sized,
@@ -117,10 +630,24 @@ where
     let mut min_distance = f32::INFINITY;
     let mut min_normal = cgmath::vec2(0.0, 0.0);
 
+    // The normals have to come from the winding of the polytype, when the origin is on an edge
+    // the side the origin is on cant tell which way is outwards
+    let winding = {
+        let (a, b, c) = (polytype[0], polytype[1], polytype[2]);
+        let ab = b - a;
+        let ac = c - a;
+        if ab.x * ac.y - ab.y * ac.x < 0.0 {
+            -1.0
+        } else {
+            1.0
+        }
+    };
+
     let mut iterations = 0;
     while min_distance == f32::INFINITY {
-        if iterations > MAX_PHYSICS_ITERATIONS {
-            println!("Warning: reached maximum physics iterations when finding collision info, assuming there is no collision");
+        if iterations > params.max_iterations {
+            // Assume there is no collision
+            *cap_hits += 1;
             return None;
         }
 
@@ -129,15 +656,14 @@ where
             let vertex_j = polytype[j];
 
             let ij = vertex_j - vertex_i;
-
-            let mut normal = cgmath::vec2(ij.y, -ij.x).normalize();
-            let mut distance = normal.dot(vertex_i);
-
-            if distance < 0.0 {
-                distance *= -1.0;
-                normal *= -1.0;
+            // Edges that have collapsed to a point have no normal
+            if ij.is_zero() {
+                continue;
             }
 
+            let normal = cgmath::vec2(ij.y, -ij.x).normalize() * winding;
+            let distance = normal.dot(vertex_i);
+
             if distance < min_distance {
                 min_distance = distance;
                 min_normal = normal;
@@ -148,7 +674,9 @@ where
         let support = support(c1, c2, min_normal);
         let s_distance = min_normal.dot(support);
 
-        if (s_distance - min_distance).abs() > 0.001 {
+        // A support point that is already part of the polytype means the edge cant be expanded any further
+        if (s_distance - min_distance).abs() > params.epa_tolerance && !polytype.contains(&support)
+        {
             min_distance = f32::INFINITY;
             polytype.insert(min_index, support);
         }
@@ -158,6 +686,383 @@ where
 
     Some(Collision {
         normal: min_normal,
-        depth: min_distance + 0.001,
+        depth: min_distance + params.epa_tolerance,
+        contact_points: contact_points(c1, c2, min_normal, PENETRATION_SLOP),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Quad;
+
+    // Its support point is always at a right angle to the direction, so GJK never gets any closer to the origin but
+    // never finds it has gone past it either, it goes around for thousands of iterations before rounding lets it out.
+    // Scaling by a power of 2 keeps the dot product exactly 0
+    struct Spinning;
+
+    impl Collider for Spinning {
+        fn center(&self) -> cgmath::Vector2<f32> {
+            cgmath::vec2(0.0, 0.0)
+        }
+
+        fn furthest_point_in_direction(
+            &self,
+            direction: cgmath::Vector2<f32>,
+        ) -> cgmath::Vector2<f32> {
+            let scale = 2.0f32.powi(-(direction.magnitude().log2().round() as i32));
+            cgmath::vec2(-direction.y, direction.x) * scale
+        }
+    }
+
+    struct Point;
+
+    impl Collider for Point {
+        fn center(&self) -> cgmath::Vector2<f32> {
+            cgmath::vec2(0.0, 0.0)
+        }
+
+        fn furthest_point_in_direction(
+            &self,
+            _direction: cgmath::Vector2<f32>,
+        ) -> cgmath::Vector2<f32> {
+            cgmath::vec2(0.0, 0.0)
+        }
+    }
+
+    #[test]
+    fn orient_towards_flips_normals_pointing_away() {
+        let mut collision = Collision {
+            normal: cgmath::vec2(-1.0, 0.0),
+            depth: 0.5,
+            contact_points: ArrayVec::new(),
+        };
+        assert!(collision.orient_towards(cgmath::vec2(1.0, 0.2)));
+        assert_eq!(collision.normal, cgmath::vec2(1.0, 0.0));
+        assert!(!collision.orient_towards(cgmath::vec2(1.0, -0.2)));
+        assert_eq!(collision.normal, cgmath::vec2(1.0, 0.0));
+    }
+
+    #[test]
+    fn distance_between_touching_apart_and_overlapping_quads() {
+        let params = CollisionParams::default();
+        let a = Quad::default();
+        // Face to face, sharing the face at x = 0.5 between y = -0.3 and 0.5
+        let touching = Quad {
+            position: cgmath::vec2(1.0, 0.2),
+            ..Quad::default()
+        };
+        let mut cap_hits = 0;
+        let (distance, closest_a, closest_b) =
+            distance_between(&a, &touching, &params, &mut cap_hits).unwrap();
+        assert_eq!(distance, 0.0);
+        for closest in [closest_a, closest_b] {
+            assert!((closest.x - 0.5).abs() < 0.0001, "{closest:?}");
+            assert!((-0.3..=0.5).contains(&closest.y), "{closest:?}");
+        }
+
+        let apart = Quad {
+            position: cgmath::vec2(1.25, 0.2),
+            ..Quad::default()
+        };
+        let (distance, closest_a, closest_b) =
+            distance_between(&a, &apart, &params, &mut cap_hits).unwrap();
+        assert!((distance - 0.25).abs() < 0.0001, "{distance}");
+        assert!((closest_a.x - 0.5).abs() < 0.0001 && (closest_b.x - 0.75).abs() < 0.0001);
+        assert!((closest_a.y - closest_b.y).abs() < 0.0001);
+
+        let overlapping = Quad {
+            position: cgmath::vec2(0.7, 0.2),
+            rotation: 0.4,
+            ..Quad::default()
+        };
+        assert!(distance_between(&a, &overlapping, &params, &mut cap_hits).is_none());
+        assert_eq!(cap_hits, 0);
+    }
+
+    #[test]
+    fn gjk_gives_up_after_max_iterations() {
+        let params = CollisionParams::default();
+        let mut cap_hits = 0;
+        assert!(gjk(&Spinning, &Point, &params, &mut cap_hits).is_none());
+        assert_eq!(cap_hits, 1);
+        assert!(get_collision(&Spinning, &Point, &params, &mut cap_hits).is_none());
+        assert_eq!(cap_hits, 2);
+
+        // The other GJK loops give up after the same number of iterations
+        let params = CollisionParams {
+            max_iterations: 0,
+            ..CollisionParams::default()
+        };
+        let apart = Quad {
+            position: cgmath::vec2(2.0, 0.0),
+            ..Quad::default()
+        };
+        assert!(distance_between(&Quad::default(), &apart, &params, &mut cap_hits).is_none());
+        assert_eq!(cap_hits, 3);
+        assert!(raycast(
+            &[apart],
+            cgmath::vec2(0.0, 0.0),
+            cgmath::vec2(1.0, 0.0),
+            10.0,
+            &params
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn concentric_quads_collide() {
+        let params = CollisionParams::default();
+        for (rotation, scale) in [(0.0, cgmath::vec2(1.0, 1.0)), (0.3, cgmath::vec2(2.0, 1.0))] {
+            let quad = Quad {
+                position: cgmath::vec2(1.0, -2.0),
+                rotation,
+                scale,
+                ..Quad::default()
+            };
+            let mut cap_hits = 0;
+            let collision = get_collision(&quad, &quad, &params, &mut cap_hits).unwrap();
+            assert_eq!(cap_hits, 0);
+            assert!(
+                (collision.normal.magnitude() - 1.0).abs() < 0.001,
+                "{collision:?}"
+            );
+            // Pushed out along the shortest side
+            let depth = collision.depth - params.epa_tolerance;
+            assert!(
+                (depth - scale.y).abs() <= params.epa_tolerance,
+                "{collision:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn origin_on_a_simplex_edge() {
+        let params = CollisionParams::default();
+        let a = Quad::default();
+        // The first two support points are straight above and below the origin, so the line between them goes right
+        // through it and has no side to search towards
+        let b = Quad {
+            position: cgmath::vec2(0.0, 0.5),
+            ..Quad::default()
+        };
+        let mut cap_hits = 0;
+        let first = support(&a, &b, b.position - a.position);
+        let second = support(&a, &b, -first);
+        assert_eq!(first.x, 0.0);
+        assert_eq!(second.x, 0.0);
+        assert!(first.y > 0.0 && second.y < 0.0);
+
+        let collision = get_collision(&a, &b, &params, &mut cap_hits).unwrap();
+        assert_eq!(cap_hits, 0);
+        assert!(collision.normal.x.is_finite() && collision.depth.is_finite());
+        assert!(
+            (collision.normal - cgmath::vec2(0.0, 1.0)).magnitude() < 0.001,
+            "{collision:?}"
+        );
+        assert!((collision.depth - params.epa_tolerance - 0.5).abs() <= params.epa_tolerance);
+    }
+
+    // How far the point is from the edge of the quad, inside or outside
+    fn distance_to_boundary(quad: &Quad, point: cgmath::Vector2<f32>) -> f32 {
+        let offset = point - quad.position;
+        let (sin, cos) = quad.rotation.sin_cos();
+        let local = cgmath::vec2(
+            offset.x * cos - offset.y * sin,
+            offset.y * cos + offset.x * sin,
+        );
+        let outside = cgmath::vec2(
+            local.x.abs() - quad.scale.x * 0.5,
+            local.y.abs() - quad.scale.y * 0.5,
+        );
+        if outside.x > 0.0 || outside.y > 0.0 {
+            cgmath::vec2(outside.x.max(0.0), outside.y.max(0.0)).magnitude()
+        } else {
+            -outside.x.max(outside.y)
+        }
+    }
+
+    fn assert_contacts_on_both(a: &Quad, b: &Quad, expected_points: usize) {
+        let collision = get_collision(a, b, &CollisionParams::default(), &mut 0).unwrap();
+        assert_eq!(
+            collision.contact_points.len(),
+            expected_points,
+            "{collision:?}"
+        );
+        for &point in &collision.contact_points {
+            assert!(
+                distance_to_boundary(a, point) < 0.001,
+                "{point:?} isnt on {a:?}"
+            );
+            assert!(
+                distance_to_boundary(b, point) < 0.001,
+                "{point:?} isnt on {b:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn face_contact_points_are_on_both_quads() {
+        let floor = Quad {
+            scale: cgmath::vec2(10.0, 1.0),
+            ..Quad::default()
+        };
+        let resting = Quad {
+            position: cgmath::vec2(1.0, 0.9995),
+            ..Quad::default()
+        };
+        assert_contacts_on_both(&floor, &resting, 2);
+
+        let side = Quad {
+            position: cgmath::vec2(5.4995, 0.25),
+            ..Quad::default()
+        };
+        assert_contacts_on_both(&floor, &side, 2);
+    }
+
+    #[test]
+    fn corner_contact_point_is_on_both_quads() {
+        let floor = Quad {
+            scale: cgmath::vec2(10.0, 1.0),
+            ..Quad::default()
+        };
+        for rotation in [0.3f32, std::f32::consts::FRAC_PI_4, -1.0] {
+            let mut corner = Quad {
+                rotation,
+                ..Quad::default()
+            };
+            let lowest = corner.furthest_point_in_direction(cgmath::vec2(0.0, -1.0));
+            corner.position.y = 0.4995 - lowest.y;
+            assert_contacts_on_both(&floor, &corner, 1);
+            assert_contacts_on_both(&corner, &floor, 1);
+        }
+    }
+
+    #[test]
+    fn depth_is_stable_for_tiny_and_huge_quads() {
+        for size in [0.01f32, 100.0] {
+            let params = CollisionParams {
+                epa_tolerance: size * 0.001,
+                ..CollisionParams::default()
+            };
+            let a = Quad {
+                scale: cgmath::vec2(size, size),
+                ..Quad::default()
+            };
+            for overlap in [0.01, 0.1, 0.4] {
+                // Moving along the face shouldnt change the depth
+                for slide in [-0.3, 0.0, 0.2] {
+                    let b = Quad {
+                        position: cgmath::vec2(1.0 - overlap, slide) * size,
+                        scale: cgmath::vec2(size, size),
+                        ..Quad::default()
+                    };
+                    let collision = get_collision(&a, &b, &params, &mut 0).unwrap();
+                    let depth = collision.depth - params.epa_tolerance;
+                    assert!(
+                        (depth - overlap * size).abs() <= params.epa_tolerance,
+                        "size {size}: depth {depth} for an overlap of {}",
+                        overlap * size
+                    );
+                    assert!((collision.normal - cgmath::vec2(1.0, 0.0)).magnitude() < 0.001);
+                }
+            }
+        }
+    }
+}
+
+// Random pairs of quads checked against the separating axis theorem, which is exact for boxes and shares no code
+// with GJK and EPA. The seeds are fixed, so a failing seed can be run again on its own
+#[cfg(test)]
+mod property_tests {
+    use super::*;
+    use crate::Quad;
+    use rand::{Rng, SeedableRng};
+
+    // How far `b` has to move to stop overlapping `a`, negative when they are apart
+    fn sat(a: &Quad, b: &Quad) -> f32 {
+        let (corners_a, corners_b) = (a.corners(), b.corners());
+        // Each quad only has 2 different edge directions
+        let axes = [&corners_a, &corners_b].into_iter().flat_map(|corners| {
+            [(0, 1), (0, 2)].map(|(i, j)| {
+                let edge = corners[j] - corners[i];
+                cgmath::vec2(-edge.y, edge.x).normalize()
+            })
+        });
+        let project = |corners: &[cgmath::Vector2<f32>; 4], axis: cgmath::Vector2<f32>| {
+            corners
+                .iter()
+                .map(|corner| corner.dot(axis))
+                .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), x| {
+                    (min.min(x), max.max(x))
+                })
+        };
+        axes.map(|axis| {
+            let (min_a, max_a) = project(&corners_a, axis);
+            let (min_b, max_b) = project(&corners_b, axis);
+            (max_a - min_b).min(max_b - min_a)
+        })
+        .fold(f32::INFINITY, f32::min)
+    }
+
+    fn random_quad(rng: &mut impl Rng) -> Quad {
+        Quad {
+            position: cgmath::vec2(rng.gen_range(-1.5..1.5), rng.gen_range(-1.5..1.5)),
+            // Some of them lined up with the axes, where faces are exactly parallel
+            rotation: if rng.gen_bool(0.2) {
+                0.0
+            } else {
+                rng.gen_range(-3.2..3.2)
+            },
+            scale: cgmath::vec2(rng.gen_range(0.1..3.0), rng.gen_range(0.1..3.0)),
+            ..Quad::default()
+        }
+    }
+
+    #[test]
+    fn get_collision_agrees_with_sat() {
+        let params = CollisionParams::default();
+        for seed in 0..5000 {
+            let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+            let a = random_quad(&mut rng);
+            let b = random_quad(&mut rng);
+            let sat_depth = sat(&a, &b);
+            let mut cap_hits = 0;
+            let collision = get_collision(&a, &b, &params, &mut cap_hits);
+            assert_eq!(cap_hits, 0, "seed {seed}: hit the iteration cap");
+
+            // Pairs that are only just touching could go either way
+            let Some(collision) = collision else {
+                assert!(
+                    sat_depth <= params.epa_tolerance,
+                    "seed {seed}: missed an overlap of {sat_depth}"
+                );
+                continue;
+            };
+            assert!(
+                sat_depth >= -params.epa_tolerance,
+                "seed {seed}: found an overlap but they are {} apart",
+                -sat_depth
+            );
+            assert!(
+                collision.normal.x.is_finite() && collision.depth.is_finite(),
+                "seed {seed}: {collision:?}"
+            );
+
+            let depth = collision.depth - params.epa_tolerance;
+            assert!(
+                (depth - sat_depth).abs() <= params.epa_tolerance,
+                "seed {seed}: depth {depth} but sat found {sat_depth}"
+            );
+            let pushed = Quad {
+                position: b.position + collision.normal * collision.depth,
+                ..b
+            };
+            let depth_after = sat(&a, &pushed);
+            assert!(
+                depth_after <= 0.0001,
+                "seed {seed}: still overlapping by {depth_after} after being pushed out by {collision:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,71 @@
+struct VertexInput {
+    @builtin(vertex_index) vertex_index: u32,
+};
+
+struct VertexOutput {
+    @location(0) clip: vec2<f32>,
+    @builtin(position) clip_position: vec4<f32>,
+};
+
+struct Camera {
+    position: vec2<f32>,
+    rotation: f32,
+    zoom: f32,
+    screen_size: vec2<f32>,
+};
+
+@group(0)
+@binding(0)
+var<uniform> camera: Camera;
+
+struct Grid {
+    spacing: f32,
+};
+
+@group(1)
+@binding(0)
+var<uniform> grid: Grid;
+
+@vertex
+fn vs_main(model: VertexInput) -> VertexOutput {
+    var output: VertexOutput;
+
+    // A single triangle that covers the whole viewport
+    let uv = vec2<f32>(
+        f32((model.vertex_index << 1u) & 2u),
+        f32(model.vertex_index & 2u),
+    );
+
+    output.clip = uv * 2.0 - 1.0;
+    output.clip_position = vec4<f32>(output.clip, 0.0, 1.0);
+    return output;
+}
+
+// How much of the pixel is covered by a line at every whole number of `coordinate`, `width` is how much it changes
+// over the pixel. That has to be found in `fs_main`, the gl backend puts this function in the vertex shader too
+// where `fwidth` doesnt exist
+fn lines(coordinate: vec2<f32>, width: vec2<f32>) -> f32 {
+    let distance = abs(fract(coordinate - 0.5) - 0.5) / width;
+    let coverage = 1.0 - min(min(distance.x, distance.y), 1.0);
+    // Lines closer together than a few pixels would just fill the screen, so fade them out
+    let fade = 1.0 - clamp(max(width.x, width.y) * 4.0 - 0.5, 0.0, 1.0);
+    return coverage * fade;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // The inverse of the camera transform in `vs_main` of the quad shader
+    let aspect = camera.screen_size.x / camera.screen_size.y;
+    let rotated = vec2<f32>(in.clip.x * aspect, in.clip.y);
+    let zoomed = vec2<f32>(
+        rotated.x * cos(camera.rotation) + rotated.y * sin(camera.rotation),
+        rotated.y * cos(camera.rotation) - rotated.x * sin(camera.rotation),
+    );
+    let world_position = zoomed / camera.zoom + camera.position;
+
+    let minor_coordinate = world_position / grid.spacing;
+    let major_coordinate = minor_coordinate / 5.0;
+    let minor = lines(minor_coordinate, fwidth(minor_coordinate)) * 0.1;
+    let major = lines(major_coordinate, fwidth(major_coordinate)) * 0.25;
+    return vec4<f32>(1.0, 1.0, 1.0, max(minor, major));
+}
@@ -0,0 +1,54 @@
+use serde::{Deserialize, Serialize};
+
+// Keeps the anchors of two quads `rest_length` apart, like a rod between them.
+// The anchors are relative to the quads, so they rotate with them
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Joint {
+    pub body_a: usize,
+    pub body_b: usize,
+    pub anchor_a: cgmath::Vector2<f32>,
+    pub anchor_b: cgmath::Vector2<f32>,
+    pub rest_length: f32,
+}
+
+impl Joint {
+    // The other body and both anchors from the side of `body`, if the joint is attached to it
+    pub fn from_side_of(
+        &self,
+        body: usize,
+    ) -> Option<(usize, cgmath::Vector2<f32>, cgmath::Vector2<f32>)> {
+        if self.body_a == self.body_b {
+            None
+        } else if self.body_a == body {
+            Some((self.body_b, self.anchor_a, self.anchor_b))
+        } else if self.body_b == body {
+            Some((self.body_a, self.anchor_b, self.anchor_a))
+        } else {
+            None
+        }
+    }
+}
+
+// A soft connection between the centers of two quads, it stretches and wobbles instead of holding
+// the length like a `Joint`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SpringJoint {
+    pub body_a: usize,
+    pub body_b: usize,
+    pub rest_length: f32,
+    pub stiffness: f32,
+    pub damping: f32,
+}
+
+impl SpringJoint {
+    // Any stiffer and a single step overshoots the rest length by more than it started with,
+    // so the spring gains energy every step until it explodes
+    pub fn max_stiffness(inverse_mass: f32, ts: f32) -> f32 {
+        1.0 / (inverse_mass * ts * ts)
+    }
+
+    // Any more damping and a single step reverses the velocity instead of slowing it down
+    pub fn max_damping(inverse_mass: f32, ts: f32) -> f32 {
+        1.0 / (inverse_mass * ts)
+    }
+}
@@ -1,15 +1,67 @@
 #![deny(elided_lifetimes_in_paths)]
 
+#[cfg(feature = "gui")]
 mod app;
+mod broadphase;
+mod camera;
+mod capsule;
+mod clip;
 mod collision;
+mod joint;
 mod quad;
+#[cfg(feature = "gui")]
 mod renderer;
+mod resolution;
+mod scene;
 mod sweeping_collider;
+#[cfg(feature = "gui")]
+mod texture;
+mod world;
 
+#[cfg(feature = "gui")]
 pub use app::*;
+pub use broadphase::*;
+pub use camera::*;
+pub use capsule::*;
+pub use clip::*;
 pub use collision::*;
+pub use joint::*;
 pub use quad::*;
+#[cfg(feature = "gui")]
 pub(crate) use renderer::*;
+pub use resolution::*;
+pub use scene::*;
 pub use sweeping_collider::*;
+#[cfg(feature = "gui")]
+pub(crate) use texture::*;
+pub use world::*;
 
 const MAX_PHYSICS_ITERATIONS: usize = 100;
+#[cfg(feature = "gui")]
+const MAX_FIXED_UPDATES_PER_FRAME: usize = 8;
+const PENETRATION_SLOP: f32 = 0.01;
+const IMPULSE_SLOP: f32 = 0.0001;
+const GJK_TOLERANCE: f32 = 0.0001;
+#[cfg(feature = "gui")]
+const STUCK_STEPS_WARNING: u32 = 100;
+const JITTER_ANGLE: f32 = 0.001;
+// How many steps a spinning collider's sweep is split into. Each support point costs one more support point of the
+// collider per step, and fewer steps push the sweep further out past the arcs the corners really swing along
+const ANGULAR_SWEEP_SAMPLES: usize = 8;
+// How much of last step's impulse a contact starts with, all of it overshoots and can knock stacks over
+const WARM_START_FACTOR: f32 = 0.8;
+const FLASH_DURATION: f32 = 0.25;
+// How much bigger than the framed bounds the view is, so nothing sits right on the edge
+const CAMERA_FRAME_MARGIN: f32 = 1.1;
+#[cfg(feature = "gui")]
+const CAMERA_SHAKE_DURATION: f32 = 0.3;
+#[cfg(feature = "gui")]
+const CAMERA_SHAKE_AMOUNT: f32 = 0.02;
+// How many world units long a debug drawn normal is per unit of depth
+#[cfg(feature = "gui")]
+const DEBUG_NORMAL_SCALE: f32 = 10.0;
+// The impulse given per world unit that a quad is pulled back with the right mouse button
+#[cfg(feature = "gui")]
+const SLINGSHOT_STRENGTH: f32 = 5.0;
+#[cfg(feature = "gui")]
+const MAX_UNDO_HISTORY: usize = 64;
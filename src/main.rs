@@ -1,12 +1,49 @@
-use eframe::wgpu;
+use eframe::{egui, wgpu};
 use physics::App;
 
+// Parses `--size WIDTHxHEIGHT` and `--position X,Y`, these are only used when there is no window state saved from a previous run
+fn parse_window_args() -> (Option<egui::Vec2>, Option<egui::Pos2>) {
+    fn parse_pair(value: &str, separator: char) -> Option<(f32, f32)> {
+        let (a, b) = value.split_once(separator)?;
+        Some((a.trim().parse().ok()?, b.trim().parse().ok()?))
+    }
+
+    let mut size = None;
+    let mut position = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--size" => {
+                let value = args.next().unwrap_or_default();
+                let Some((width, height)) = parse_pair(&value, 'x') else {
+                    eprintln!("Expected --size WIDTHxHEIGHT, got '{value}'");
+                    continue;
+                };
+                size = Some(egui::vec2(width, height));
+            }
+            "--position" => {
+                let value = args.next().unwrap_or_default();
+                let Some((x, y)) = parse_pair(&value, ',') else {
+                    eprintln!("Expected --position X,Y, got '{value}'");
+                    continue;
+                };
+                position = Some(egui::pos2(x, y));
+            }
+            _ => eprintln!("Unknown argument '{arg}'"),
+        }
+    }
+    (size, position)
+}
+
 fn main() {
+    let (initial_window_size, initial_window_pos) = parse_window_args();
     eframe::run_native(
         "Physics",
         eframe::NativeOptions {
             renderer: eframe::Renderer::Wgpu,
             vsync: false,
+            initial_window_size,
+            initial_window_pos,
             wgpu_options: eframe::egui_wgpu::WgpuConfiguration {
                 power_preference: eframe::wgpu::PowerPreference::HighPerformance,
                 device_descriptor: wgpu::DeviceDescriptor {
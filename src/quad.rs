@@ -1,9 +1,82 @@
 use cgmath::prelude::*;
-use serde::{Serialize, Deserialize};
+use serde::{Deserialize, Deserializer, Serialize};
 
-use crate::Collider;
+use crate::{Aabb, Collider, Face};
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BodyState {
+    pub position: cgmath::Vector2<f32>,
+    pub velocity: cgmath::Vector2<f32>,
+    pub rotation: f32,
+    pub angular_velocity: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum BodyType {
+    #[default]
+    Dynamic,
+    Static,
+    // Moves with its velocity but nothing pushes it, like a static quad that is allowed to move
+    Kinematic,
+}
+
+impl BodyType {
+    pub const ALL: [BodyType; 3] = [Self::Dynamic, Self::Static, Self::Kinematic];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Dynamic => "Dynamic",
+            Self::Static => "Static",
+            Self::Kinematic => "Kinematic",
+        }
+    }
+}
+
+// Quads and capsules used to be saved with `dynamic: bool`, so that still loads
+pub(crate) fn deserialize_body_type<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<BodyType, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum BodyTypeOrDynamic {
+        BodyType(BodyType),
+        Dynamic(bool),
+    }
+
+    Ok(match BodyTypeOrDynamic::deserialize(deserializer)? {
+        BodyTypeOrDynamic::BodyType(body_type) => body_type,
+        BodyTypeOrDynamic::Dynamic(true) => BodyType::Dynamic,
+        BodyTypeOrDynamic::Dynamic(false) => BodyType::Static,
+    })
+}
+
+// Quads from before alpha was added are opaque
+fn default_alpha() -> f32 {
+    1.0
+}
+
+fn default_mass() -> f32 {
+    1.0
+}
+
+fn default_gravity_scale() -> f32 {
+    1.0
+}
+
+// Quads and capsules from before layers were added are on the first layer and collide with everything
+pub(crate) fn default_layer() -> u32 {
+    1
+}
+
+pub(crate) fn default_mask() -> u32 {
+    u32::MAX
+}
+
+fn default_one_way_normal() -> cgmath::Vector2<f32> {
+    cgmath::vec2(0.0, 1.0)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct Quad {
     pub position: cgmath::Vector2<f32>,
     pub velocity: cgmath::Vector2<f32>,
@@ -11,32 +84,159 @@ pub struct Quad {
     pub angular_velocity: f32,
     pub scale: cgmath::Vector2<f32>,
     pub color: cgmath::Vector3<f32>,
-    pub dynamic: bool,
+    // 0 is invisible and 1 is opaque, only changes how the quad is drawn
+    #[serde(default = "default_alpha")]
+    pub alpha: f32,
+    #[serde(default, alias = "dynamic", deserialize_with = "deserialize_body_type")]
+    pub body_type: BodyType,
+    #[serde(default = "default_mass")]
+    pub mass: f32,
+    #[serde(default)]
+    pub drag_coefficient: f32,
+    // Multiplies the world's gravity, 0 ignores it and negative values float upwards
+    #[serde(default = "default_gravity_scale")]
+    pub gravity_scale: f32,
+    // Used instead of the world's damping when set
+    pub linear_damping: Option<f32>,
+    pub angular_damping: Option<f32>,
+    #[serde(default)]
+    pub friction: f32,
+    #[serde(default)]
+    pub render_order: i32,
+    pub texture: Option<usize>,
+    pub initial: Option<BodyState>,
+    #[serde(default)]
+    pub flash_on_collision: bool,
+    // The layers the quad is on, and the layers it collides with
+    #[serde(default = "default_layer")]
+    pub layer: u32,
+    #[serde(default = "default_mask")]
+    pub mask: u32,
+    // Sensors report what overlaps them but never push anything
+    #[serde(default)]
+    pub is_sensor: bool,
+    // Only pushes quads that come at it from the side `one_way_normal` points towards, like a platform that can be
+    // jumped up through. The normal is in world space so it doesnt turn with the quad
+    #[serde(default)]
+    pub one_way: bool,
+    #[serde(default = "default_one_way_normal")]
+    pub one_way_normal: cgmath::Vector2<f32>,
+    #[serde(skip)]
+    pub flash_time: f32,
+    #[serde(skip)]
+    pub penetration_depth: f32,
+    #[serde(skip)]
+    pub stuck_steps: u32,
+    #[serde(skip)]
+    pub is_sleeping: bool,
+    // How many steps in a row the quad has been moving slower than the sleep threshold
+    #[serde(skip)]
+    pub low_energy_steps: u32,
 }
 
-impl Collider for Quad {
-    fn center(&self) -> cgmath::Vector2<f32> {
-        self.position
+impl Quad {
+    pub fn state(&self) -> BodyState {
+        BodyState {
+            position: self.position,
+            velocity: self.velocity,
+            rotation: self.rotation,
+            angular_velocity: self.angular_velocity,
+        }
     }
 
-    fn furthest_point_in_direction(&self, direction: cgmath::Vector2<f32>) -> cgmath::Vector2<f32> {
-        let points = [
+    // Static and kinematic quads have infinite mass
+    pub fn inverse_mass(&self) -> f32 {
+        if self.body_type == BodyType::Dynamic {
+            1.0 / self.mass
+        } else {
+            0.0
+        }
+    }
+
+    // Static and kinematic quads have infinite inertia
+    pub fn inverse_inertia(&self) -> f32 {
+        if self.body_type == BodyType::Dynamic {
+            12.0 / (self.mass * (self.scale.x * self.scale.x + self.scale.y * self.scale.y))
+        } else {
+            0.0
+        }
+    }
+
+    // The velocity of a point attached to the quad, positive rotation is clockwise
+    pub fn velocity_at(&self, point: cgmath::Vector2<f32>) -> cgmath::Vector2<f32> {
+        let offset = point - self.position;
+        self.velocity + cgmath::vec2(offset.y, -offset.x) * self.angular_velocity
+    }
+
+    // Instantly changes the velocity as if the impulse was applied at the point
+    pub fn apply_impulse(&mut self, impulse: cgmath::Vector2<f32>, point: cgmath::Vector2<f32>) {
+        let offset = point - self.position;
+        self.velocity += impulse * self.inverse_mass();
+        // Positive rotation is clockwise, so this is the negative of the 2d cross product
+        self.angular_velocity -=
+            (offset.x * impulse.y - offset.y * impulse.x) * self.inverse_inertia();
+        self.wake();
+    }
+
+    pub fn contains_point(&self, point: cgmath::Vector2<f32>) -> bool {
+        // Undo the rotation from `furthest_point_in_direction` to get the point in local space
+        let point = point - self.position;
+        let local = cgmath::vec2(
+            point.x * self.rotation.cos() - point.y * self.rotation.sin(),
+            point.y * self.rotation.cos() + point.x * self.rotation.sin(),
+        );
+        local.x.abs() <= self.scale.x * 0.5 && local.y.abs() <= self.scale.y * 0.5
+    }
+
+    // In the same order that `furthest_point_in_direction` checks them
+    pub fn corners(&self) -> [cgmath::Vector2<f32>; 4] {
+        [
             cgmath::vec2(-self.scale.x * 0.5, -self.scale.y * 0.5),
             cgmath::vec2(-self.scale.x * 0.5, self.scale.y * 0.5),
             cgmath::vec2(self.scale.x * 0.5, -self.scale.y * 0.5),
             cgmath::vec2(self.scale.x * 0.5, self.scale.y * 0.5),
         ]
-        .map(|point| {
-            // Rotate the points
-            cgmath::vec2(
-                point.x * (-self.rotation).cos() - point.y * (-self.rotation).sin(),
-                point.y * (-self.rotation).cos() + point.x * (-self.rotation).sin(),
-            )
-        })
-        .map(|point| {
-            // Translate the points
-            point + self.position
-        });
+        .map(|point| self.local_to_world(point))
+    }
+
+    pub fn local_to_world(&self, point: cgmath::Vector2<f32>) -> cgmath::Vector2<f32> {
+        // Rotate the point, then translate it
+        cgmath::vec2(
+            point.x * (-self.rotation).cos() - point.y * (-self.rotation).sin(),
+            point.y * (-self.rotation).cos() + point.x * (-self.rotation).sin(),
+        ) + self.position
+    }
+
+    pub fn set_state(&mut self, state: BodyState) {
+        self.position = state.position;
+        self.velocity = state.velocity;
+        self.rotation = state.rotation;
+        self.angular_velocity = state.angular_velocity;
+        self.wake();
+    }
+
+    pub fn wake(&mut self) {
+        self.is_sleeping = false;
+        self.low_energy_steps = 0;
+    }
+
+    // Only one of the quads has to want to collide with the other one
+    pub fn collides_with(&self, other: &Quad) -> bool {
+        self.layer & other.mask != 0 || other.layer & self.mask != 0
+    }
+
+    pub fn is_moving(&self, threshold: f32) -> bool {
+        self.velocity.magnitude() > threshold || self.angular_velocity.abs() > threshold
+    }
+}
+
+impl Collider for Quad {
+    fn center(&self) -> cgmath::Vector2<f32> {
+        self.position
+    }
+
+    fn furthest_point_in_direction(&self, direction: cgmath::Vector2<f32>) -> cgmath::Vector2<f32> {
+        let points = self.corners();
 
         let mut current_point = points[0];
         let mut max_dot = points[0].dot(direction);
@@ -49,6 +249,35 @@ impl Collider for Quad {
         }
         current_point
     }
+
+    fn aabb(&self) -> Aabb {
+        let corners = self.corners();
+        let start = Aabb {
+            min: corners[0],
+            max: corners[0],
+        };
+        corners[1..].iter().fold(start, |aabb, &corner| {
+            aabb.union(&Aabb {
+                min: corner,
+                max: corner,
+            })
+        })
+    }
+
+    fn face_in_direction(&self, direction: cgmath::Vector2<f32>) -> Face {
+        let corners = self.corners();
+        // The corners going counter clockwise, so the outwards normal of each edge is on its right
+        [0, 2, 3, 1, 0]
+            .windows(2)
+            .map(|edge| {
+                let a = corners[edge[0]];
+                let b = corners[edge[1]];
+                let normal = cgmath::vec2(b.y - a.y, a.x - b.x).normalize();
+                Face { a, b, normal }
+            })
+            .max_by(|a, b| a.normal.dot(direction).total_cmp(&b.normal.dot(direction)))
+            .unwrap()
+    }
 }
 
 impl Default for Quad {
@@ -60,7 +289,52 @@ impl Default for Quad {
             angular_velocity: 0.0,
             scale: cgmath::vec2(1.0, 1.0),
             color: cgmath::vec3(1.0, 1.0, 1.0),
-            dynamic: true,
+            alpha: 1.0,
+            body_type: BodyType::Dynamic,
+            mass: 1.0,
+            drag_coefficient: 0.0,
+            gravity_scale: 1.0,
+            linear_damping: None,
+            angular_damping: None,
+            friction: 0.5,
+            render_order: 0,
+            texture: None,
+            initial: None,
+            flash_on_collision: false,
+            layer: 1,
+            mask: u32::MAX,
+            is_sensor: false,
+            one_way: false,
+            one_way_normal: cgmath::vec2(0.0, 1.0),
+            flash_time: 0.0,
+            penetration_depth: 0.0,
+            stuck_steps: 0,
+            is_sleeping: false,
+            low_energy_steps: 0,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quad_from_before_any_new_fields_loads() {
+        let json = r#"{
+            "position": [1.0, 2.0],
+            "velocity": [0.0, 0.0],
+            "rotation": 0.0,
+            "angular_velocity": 0.0,
+            "scale": [1.0, 1.0],
+            "color": [1.0, 0.0, 0.0],
+            "dynamic": true
+        }"#;
+        let quad: Quad = serde_json::from_str(json).unwrap();
+        assert_eq!(quad.body_type, BodyType::Dynamic);
+        assert_eq!(quad.mass, 1.0);
+        assert_eq!(quad.layer, 1);
+        assert_eq!(quad.mask, u32::MAX);
+        assert!(!quad.is_sensor);
+    }
+}
@@ -6,7 +6,10 @@ struct VertexInput {
 struct VertexOutput {
     @location(0) world_position: vec2<f32>,
     @location(1) texture_coordinate: vec2<f32>,
-    @location(2) color: vec3<f32>,
+    @location(2) color: vec4<f32>,
+    @location(3) atlas_coordinate: vec2<f32>,
+    @location(4) @interpolate(flat) textured: u32,
+    @location(5) @interpolate(flat) circle: u32,
     @builtin(position) clip_position: vec4<f32>,
 };
 
@@ -24,8 +27,12 @@ var<uniform> camera: Camera;
 struct Quad {
     position: vec2<f32>,
     scale: vec2<f32>,
-    color: vec3<f32>,
+    color: vec4<f32>,
     rotation: f32,
+    uv_offset: vec2<f32>,
+    uv_size: vec2<f32>,
+    textured: u32,
+    circle: u32,
 };
 
 struct QuadStorageBuffer {
@@ -37,6 +44,14 @@ struct QuadStorageBuffer {
 @binding(0)
 var<storage> quad_buffer: QuadStorageBuffer;
 
+@group(2)
+@binding(0)
+var texture_atlas: texture_2d<f32>;
+
+@group(2)
+@binding(1)
+var texture_sampler: sampler;
+
 @vertex
 fn vs_main(model: VertexInput) -> VertexOutput {
     var output: VertexOutput;
@@ -50,6 +65,7 @@ fn vs_main(model: VertexInput) -> VertexOutput {
 
     output.world_position = uv - 0.5;
     output.world_position *= quad.scale;
+    // The same rotation as `Quad::local_to_world`, so what is drawn matches the collider
     output.world_position = vec2<f32>(
         output.world_position.x * cos(-quad.rotation) - output.world_position.y * sin(-quad.rotation),
         output.world_position.y * cos(-quad.rotation) + output.world_position.x * sin(-quad.rotation),
@@ -58,6 +74,10 @@ fn vs_main(model: VertexInput) -> VertexOutput {
 
     output.texture_coordinate = uv;
     output.color = quad.color;
+    // Textures are stored top to bottom but the quads uv goes bottom to top
+    output.atlas_coordinate = quad.uv_offset + vec2<f32>(uv.x, 1.0 - uv.y) * quad.uv_size;
+    output.textured = quad.textured;
+    output.circle = quad.circle;
 
     let aspect = camera.screen_size.x / camera.screen_size.y;
     let camera_relative_position = output.world_position - camera.position;
@@ -78,5 +98,13 @@ fn vs_main(model: VertexInput) -> VertexOutput {
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return vec4<f32>(in.color, 1.0);
+    // Sampling has to happen in uniform control flow, so always sample and then pick
+    let texture_color = textureSample(texture_atlas, texture_sampler, in.atlas_coordinate);
+    if in.circle != 0u && length(in.texture_coordinate - 0.5) > 0.5 {
+        discard;
+    }
+    if in.textured != 0u {
+        return vec4<f32>(texture_color.rgb, in.color.a);
+    }
+    return in.color;
 }
@@ -1,6 +1,15 @@
+// encase's `ShaderType` derive generates a `check` function per field that is never called
+#![allow(dead_code)]
+
+use std::num::NonZeroU32;
+
 use eframe::wgpu::{self, include_wgsl, util::DeviceExt};
 use encase::{ArrayLength, ShaderSize, ShaderType, StorageBuffer, UniformBuffer};
 
+use crate::TextureData;
+
+const TEXTURE_ATLAS_SIZE: u32 = 2048;
+
 #[derive(Clone, Copy, ShaderType)]
 pub struct CameraUniform {
     pub position: cgmath::Vector2<f32>,
@@ -9,12 +18,36 @@ pub struct CameraUniform {
     pub screen_size: cgmath::Vector2<f32>,
 }
 
+#[derive(Clone, Copy, ShaderType)]
+pub struct BackgroundUniform {
+    pub color: cgmath::Vector3<f32>,
+}
+
+#[derive(Clone, Copy, ShaderType)]
+pub struct GridUniform {
+    // The distance between the minor lines in world units, every 5th line is a major line
+    pub spacing: f32,
+}
+
 #[derive(Clone, Copy, ShaderType)]
 pub struct StorageBufferQuad {
     pub position: cgmath::Vector2<f32>,
     pub scale: cgmath::Vector2<f32>,
-    pub color: cgmath::Vector3<f32>,
+    // Rgba, encase pads around it to match the alignment of a vec4 in the shader
+    pub color: cgmath::Vector4<f32>,
     pub rotation: f32,
+    pub uv_offset: cgmath::Vector2<f32>,
+    pub uv_size: cgmath::Vector2<f32>,
+    pub textured: u32,
+    // Circles fill the ellipse that fits inside the quad
+    pub circle: u32,
+}
+
+// Where a texture was placed in the texture atlas, in normalized atlas coordinates
+#[derive(Debug, Clone, Copy)]
+pub struct TextureRect {
+    pub offset: cgmath::Vector2<f32>,
+    pub size: cgmath::Vector2<f32>,
 }
 
 #[derive(Clone, ShaderType)]
@@ -24,15 +57,109 @@ pub struct QuadStorageBuffer<'a> {
     pub quads: &'a [StorageBufferQuad],
 }
 
+#[derive(Debug, Clone, Copy)]
+pub struct RendererStats {
+    pub quad_storage_buffer_capacity: usize,
+    pub quad_count: usize,
+    pub reallocated_last_prepare: bool,
+    pub sample_count: u32,
+}
+
 pub(crate) struct Renderer {
     camera_uniform_buffer: wgpu::Buffer,
     camera_bind_group: wgpu::BindGroup,
+    background_uniform_buffer: wgpu::Buffer,
+    background_bind_group: wgpu::BindGroup,
+    background_pipeline_descriptor: PipelineDescriptor,
+    background_pipeline: wgpu::RenderPipeline,
+    grid_uniform_buffer: wgpu::Buffer,
+    grid_bind_group: wgpu::BindGroup,
+    grid_pipeline_descriptor: PipelineDescriptor,
+    grid_pipeline: wgpu::RenderPipeline,
+    grid_enabled: bool,
+    quad_pipeline_descriptor: PipelineDescriptor,
     quad_pipeline: wgpu::RenderPipeline,
     quad_storage_buffer: wgpu::Buffer,
     quad_bind_group_layout: wgpu::BindGroupLayout,
     quad_bind_group: wgpu::BindGroup,
     quad_storage_buffer_capacity: usize,
     quad_count: usize,
+    reallocated_last_prepare: bool,
+    texture_atlas: wgpu::Texture,
+    texture_bind_group: wgpu::BindGroup,
+    texture_atlas_cursor: cgmath::Vector2<u32>,
+    texture_atlas_row_height: u32,
+    target_format: wgpu::TextureFormat,
+    sample_count: u32,
+    scene_target: Option<SceneTarget>,
+    scene_sampler: wgpu::Sampler,
+    blit_bind_group_layout: wgpu::BindGroupLayout,
+    blit_pipeline: wgpu::RenderPipeline,
+}
+
+// What is needed to make a pipeline again when the sample count changes
+struct PipelineDescriptor {
+    label: &'static str,
+    layout: wgpu::PipelineLayout,
+    shader: wgpu::ShaderModule,
+    topology: wgpu::PrimitiveTopology,
+    front_face: wgpu::FrontFace,
+    blend: Option<wgpu::BlendState>,
+}
+
+impl PipelineDescriptor {
+    fn create(
+        &self,
+        device: &wgpu::Device,
+        target_format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> wgpu::RenderPipeline {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(self.label),
+            layout: Some(&self.layout),
+            vertex: wgpu::VertexState {
+                module: &self.shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &self.shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: target_format,
+                    blend: self.blend,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: self.topology,
+                strip_index_format: None, // this will be needed if using an index buffer
+                front_face: self.front_face,
+                cull_mode: None, // culling is not needed
+                unclipped_depth: false,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        })
+    }
+}
+
+// The scene is drawn into its own texture so it can be multisampled, then resolved and copied into egui's render pass
+struct SceneTarget {
+    size: [u32; 2],
+    sample_count: u32,
+    // Only there when the sample count is more than 1
+    multisampled_view: Option<wgpu::TextureView>,
+    resolved_texture: wgpu::Texture,
+    resolved_view: wgpu::TextureView,
+    blit_bind_group: wgpu::BindGroup,
 }
 
 impl Renderer {
@@ -52,7 +179,8 @@ impl Renderer {
                 label: Some("Camera Bind Group Layout"),
                 entries: &[wgpu::BindGroupLayoutEntry {
                     binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX,
+                    // The grid needs the camera to find the world position of each pixel
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
                     ty: wgpu::BindingType::Buffer {
                         ty: wgpu::BufferBindingType::Uniform,
                         has_dynamic_offset: false,
@@ -71,6 +199,106 @@ impl Renderer {
             }],
         });
 
+        let background_uniform_buffer =
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Background Uniform Buffer"),
+                contents: &[0; BackgroundUniform::SHADER_SIZE.get() as _],
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::UNIFORM,
+            });
+
+        let background_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Background Bind Group Layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: Some(BackgroundUniform::SHADER_SIZE),
+                    },
+                    count: None,
+                }],
+            });
+
+        let background_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Background Bind Group"),
+            layout: &background_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: background_uniform_buffer.as_entire_binding(),
+            }],
+        });
+
+        let background_shader =
+            device.create_shader_module(include_wgsl!("./background_shader.wgsl"));
+
+        let background_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Background Pipeline Layout"),
+                bind_group_layouts: &[&background_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        let background_pipeline_descriptor = PipelineDescriptor {
+            label: "Background Pipeline",
+            layout: background_pipeline_layout,
+            shader: background_shader,
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            front_face: wgpu::FrontFace::Ccw,
+            blend: None,
+        };
+        let background_pipeline = background_pipeline_descriptor.create(device, target_format, 1);
+
+        let grid_uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Grid Uniform Buffer"),
+            contents: &[0; GridUniform::SHADER_SIZE.get() as _],
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::UNIFORM,
+        });
+
+        let grid_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Grid Bind Group Layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: Some(GridUniform::SHADER_SIZE),
+                    },
+                    count: None,
+                }],
+            });
+
+        let grid_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Grid Bind Group"),
+            layout: &grid_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: grid_uniform_buffer.as_entire_binding(),
+            }],
+        });
+
+        let grid_shader = device.create_shader_module(include_wgsl!("./grid_shader.wgsl"));
+
+        let grid_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Grid Pipeline Layout"),
+            bind_group_layouts: &[&camera_bind_group_layout, &grid_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let grid_pipeline_descriptor = PipelineDescriptor {
+            label: "Grid Pipeline",
+            layout: grid_pipeline_layout,
+            shader: grid_shader,
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            front_face: wgpu::FrontFace::Ccw,
+            // The lines are blended over the background
+            blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+        };
+        let grid_pipeline = grid_pipeline_descriptor.create(device, target_format, 1);
+
         let quad_storage_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Quad Storage Buffer"),
             contents: &[0; QuadStorageBuffer::METADATA.min_size().get() as _],
@@ -101,64 +329,341 @@ impl Renderer {
             }],
         });
 
+        let texture_atlas = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Texture Atlas"),
+            size: wgpu::Extent3d {
+                width: TEXTURE_ATLAS_SIZE,
+                height: TEXTURE_ATLAS_SIZE,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+
+        let texture_atlas_view = texture_atlas.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let texture_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Texture Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let texture_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Texture Bind Group Layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                ],
+            });
+
+        let texture_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Texture Bind Group"),
+            layout: &texture_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture_atlas_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&texture_sampler),
+                },
+            ],
+        });
+
         let quad_shader = device.create_shader_module(include_wgsl!("./quad_shader.wgsl"));
 
         let quad_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("Quad Pipeline Layout"),
-            bind_group_layouts: &[&camera_bind_group_layout, &quad_bind_group_layout],
+            bind_group_layouts: &[
+                &camera_bind_group_layout,
+                &quad_bind_group_layout,
+                &texture_bind_group_layout,
+            ],
             push_constant_ranges: &[],
         });
 
-        let quad_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Quad Pipeline"),
-            layout: Some(&quad_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &quad_shader,
-                entry_point: "vs_main",
-                buffers: &[],
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &quad_shader,
-                entry_point: "fs_main",
-                targets: &[Some(target_format.into())],
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleStrip,
-                strip_index_format: None, // this will be needed if using an index buffer
-                front_face: wgpu::FrontFace::Cw,
-                cull_mode: None, // culling is not needed
-                unclipped_depth: false,
-                polygon_mode: wgpu::PolygonMode::Fill,
-                conservative: false,
-            },
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            multiview: None,
+        let quad_pipeline_descriptor = PipelineDescriptor {
+            label: "Quad Pipeline",
+            layout: quad_pipeline_layout,
+            shader: quad_shader,
+            topology: wgpu::PrimitiveTopology::TriangleStrip,
+            front_face: wgpu::FrontFace::Cw,
+            blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+        };
+        let quad_pipeline = quad_pipeline_descriptor.create(device, target_format, 1);
+
+        let scene_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Scene Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let blit_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Blit Bind Group Layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                ],
+            });
+
+        let blit_shader = device.create_shader_module(include_wgsl!("./blit_shader.wgsl"));
+
+        let blit_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Blit Pipeline Layout"),
+            bind_group_layouts: &[&blit_bind_group_layout],
+            push_constant_ranges: &[],
         });
 
+        // egui's render pass is never multisampled, the scene is resolved before it gets here
+        let blit_pipeline = PipelineDescriptor {
+            label: "Blit Pipeline",
+            layout: blit_pipeline_layout,
+            shader: blit_shader,
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            front_face: wgpu::FrontFace::Ccw,
+            blend: None,
+        }
+        .create(device, target_format, 1);
+
         Self {
             camera_uniform_buffer,
             camera_bind_group,
+            background_uniform_buffer,
+            background_bind_group,
+            background_pipeline_descriptor,
+            background_pipeline,
+            grid_uniform_buffer,
+            grid_bind_group,
+            grid_pipeline_descriptor,
+            grid_pipeline,
+            grid_enabled: false,
+            quad_pipeline_descriptor,
             quad_pipeline,
             quad_storage_buffer,
             quad_bind_group_layout,
             quad_bind_group,
             quad_storage_buffer_capacity: 0,
             quad_count: 0,
+            reallocated_last_prepare: false,
+            texture_atlas,
+            texture_bind_group,
+            texture_atlas_cursor: cgmath::vec2(0, 0),
+            texture_atlas_row_height: 0,
+            target_format,
+            sample_count: 1,
+            scene_target: None,
+            scene_sampler,
+            blit_bind_group_layout,
+            blit_pipeline,
         }
     }
 
+    // Packs the texture into the atlas row by row, returns `None` if it doesnt fit anymore
+    pub fn add_texture(
+        &mut self,
+        queue: &wgpu::Queue,
+        texture: &TextureData,
+    ) -> Option<TextureRect> {
+        // Leave a gap between textures so filtering doesnt bleed between them
+        const PADDING: u32 = 1;
+
+        if self.texture_atlas_cursor.x + texture.width > TEXTURE_ATLAS_SIZE {
+            self.texture_atlas_cursor.x = 0;
+            self.texture_atlas_cursor.y += self.texture_atlas_row_height + PADDING;
+            self.texture_atlas_row_height = 0;
+        }
+        if self.texture_atlas_cursor.x + texture.width > TEXTURE_ATLAS_SIZE
+            || self.texture_atlas_cursor.y + texture.height > TEXTURE_ATLAS_SIZE
+        {
+            return None;
+        }
+
+        let origin = self.texture_atlas_cursor;
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &self.texture_atlas,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: origin.x,
+                    y: origin.y,
+                    z: 0,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            &texture.rgba,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: NonZeroU32::new(texture.width * 4),
+                rows_per_image: NonZeroU32::new(texture.height),
+            },
+            wgpu::Extent3d {
+                width: texture.width,
+                height: texture.height,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        self.texture_atlas_cursor.x += texture.width + PADDING;
+        self.texture_atlas_row_height = self.texture_atlas_row_height.max(texture.height);
+
+        // Inset by half a texel so linear filtering never samples outside of the texture
+        let atlas_size = TEXTURE_ATLAS_SIZE as f32;
+        Some(TextureRect {
+            offset: cgmath::vec2(origin.x as f32 + 0.5, origin.y as f32 + 0.5) / atlas_size,
+            size: cgmath::vec2(texture.width as f32 - 1.0, texture.height as f32 - 1.0)
+                / atlas_size,
+        })
+    }
+
+    // Uses the highest supported sample count that isnt more than `sample_count`, the actual count is in the stats
+    pub fn set_sample_count(&mut self, device: &wgpu::Device, sample_count: u32) {
+        // There is no adapter to ask for its format features, but wgpu only allows the guaranteed ones anyway
+        // unless `TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES` is enabled
+        let flags = self
+            .target_format
+            .describe()
+            .guaranteed_format_features
+            .flags;
+        let sample_count = [8, 4, 2, 1]
+            .into_iter()
+            .find(|&count| count <= sample_count && flags.sample_count_supported(count))
+            .unwrap_or(1);
+        if sample_count == self.sample_count {
+            return;
+        }
+
+        self.sample_count = sample_count;
+        self.background_pipeline =
+            self.background_pipeline_descriptor
+                .create(device, self.target_format, sample_count);
+        self.grid_pipeline =
+            self.grid_pipeline_descriptor
+                .create(device, self.target_format, sample_count);
+        self.quad_pipeline =
+            self.quad_pipeline_descriptor
+                .create(device, self.target_format, sample_count);
+    }
+
+    fn create_scene_target(&self, device: &wgpu::Device, size: [u32; 2]) -> SceneTarget {
+        let extent = wgpu::Extent3d {
+            width: size[0],
+            height: size[1],
+            depth_or_array_layers: 1,
+        };
+
+        let multisampled_view = (self.sample_count > 1).then(|| {
+            device
+                .create_texture(&wgpu::TextureDescriptor {
+                    label: Some("Multisampled Scene Texture"),
+                    size: extent,
+                    mip_level_count: 1,
+                    sample_count: self.sample_count,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: self.target_format,
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                    view_formats: &[],
+                })
+                .create_view(&wgpu::TextureViewDescriptor::default())
+        });
+
+        let resolved_texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Scene Texture"),
+            size: extent,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: self.target_format,
+            // Copied from for screenshots
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let resolved_view = resolved_texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let blit_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Blit Bind Group"),
+            layout: &self.blit_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&resolved_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.scene_sampler),
+                },
+            ],
+        });
+
+        SceneTarget {
+            size,
+            sample_count: self.sample_count,
+            multisampled_view,
+            resolved_texture,
+            resolved_view,
+            blit_bind_group,
+        }
+    }
+
+    // Everything for one frame is passed in together, so this has a lot of arguments
+    #[allow(clippy::too_many_arguments)]
     pub fn prepare(
         &mut self,
         camera: CameraUniform,
+        background: BackgroundUniform,
+        grid: Option<GridUniform>,
         quads: &[StorageBufferQuad],
+        // The size of the viewport in physical pixels
+        size: [u32; 2],
         device: &wgpu::Device,
         queue: &wgpu::Queue,
-        _encoder: &mut wgpu::CommandEncoder,
+        encoder: &mut wgpu::CommandEncoder,
     ) -> Vec<wgpu::CommandBuffer> {
         {
             let mut buffer = UniformBuffer::new([0; CameraUniform::SHADER_SIZE.get() as _]);
@@ -167,6 +672,21 @@ impl Renderer {
             queue.write_buffer(&self.camera_uniform_buffer, 0, &buffer);
         }
 
+        {
+            let mut buffer = UniformBuffer::new([0; BackgroundUniform::SHADER_SIZE.get() as _]);
+            buffer.write(&background).unwrap();
+            let buffer = buffer.into_inner();
+            queue.write_buffer(&self.background_uniform_buffer, 0, &buffer);
+        }
+
+        self.grid_enabled = grid.is_some();
+        if let Some(grid) = grid {
+            let mut buffer = UniformBuffer::new([0; GridUniform::SHADER_SIZE.get() as _]);
+            buffer.write(&grid).unwrap();
+            let buffer = buffer.into_inner();
+            queue.write_buffer(&self.grid_uniform_buffer, 0, &buffer);
+        }
+
         {
             let quad_storage_buffer_data = QuadStorageBuffer {
                 length: ArrayLength,
@@ -178,7 +698,8 @@ impl Renderer {
             ));
             buffer.write(&quad_storage_buffer_data).unwrap();
             let buffer = buffer.into_inner();
-            if buffer.len() > self.quad_storage_buffer_capacity {
+            self.reallocated_last_prepare = buffer.len() > self.quad_storage_buffer_capacity;
+            if self.reallocated_last_prepare {
                 self.quad_storage_buffer =
                     device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
                         label: Some("Quad Storage Buffer"),
@@ -202,13 +723,235 @@ impl Renderer {
             self.quad_count = quads.len();
         }
 
-        vec![]
-    }
+        let size = size.map(|size| size.max(1));
+        if self
+            .scene_target
+            .as_ref()
+            .is_none_or(|target| target.size != size || target.sample_count != self.sample_count)
+        {
+            self.scene_target = Some(self.create_scene_target(device, size));
+        }
+        let scene_target = self.scene_target.as_ref().unwrap();
+
+        // Without multisampling the scene is drawn straight into the texture that gets copied to the screen
+        let (view, resolve_target) = match &scene_target.multisampled_view {
+            Some(multisampled_view) => (multisampled_view, Some(&scene_target.resolved_view)),
+            None => (&scene_target.resolved_view, None),
+        };
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Scene Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+
+        render_pass.set_pipeline(&self.background_pipeline);
+        render_pass.set_bind_group(0, &self.background_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+
+        if self.grid_enabled {
+            render_pass.set_pipeline(&self.grid_pipeline);
+            render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+            render_pass.set_bind_group(1, &self.grid_bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
 
-    pub fn paint<'rp>(&'rp self, render_pass: &mut wgpu::RenderPass<'rp>) {
         render_pass.set_pipeline(&self.quad_pipeline);
         render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
         render_pass.set_bind_group(1, &self.quad_bind_group, &[]);
+        render_pass.set_bind_group(2, &self.texture_bind_group, &[]);
         render_pass.draw(0..4, 0..self.quad_count as _);
+
+        vec![]
+    }
+
+    // Reads back the last frame drawn by `prepare`, this waits for the gpu so it is slow
+    pub fn screenshot(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Result<TextureData, String> {
+        let scene_target = self
+            .scene_target
+            .as_ref()
+            .ok_or_else(|| "nothing has been drawn yet".to_string())?;
+        let bgra = match self.target_format {
+            wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+            format => return Err(format!("cant save screenshots of {format:?} textures")),
+        };
+
+        let [width, height] = scene_target.size;
+        // Each row in the buffer has to be aligned, so the rows are padded and then cropped after
+        let unpadded_bytes_per_row = width * 4;
+        let padded_bytes_per_row = unpadded_bytes_per_row
+            .div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+            * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Screenshot Buffer"),
+            size: padded_bytes_per_row as u64 * height as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Screenshot Encoder"),
+        });
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture: &scene_target.resolved_texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(padded_bytes_per_row),
+                    rows_per_image: NonZeroU32::new(height),
+                },
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+        queue.submit([encoder.finish()]);
+
+        let slice = buffer.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            _ = sender.send(result);
+        });
+        device.poll(wgpu::Maintain::Wait);
+        receiver
+            .recv()
+            .map_err(|e| e.to_string())?
+            .map_err(|e| e.to_string())?;
+
+        let rgba = slice
+            .get_mapped_range()
+            .chunks_exact(padded_bytes_per_row as _)
+            .flat_map(|row| row[..unpadded_bytes_per_row as _].chunks_exact(4))
+            // Blending leaves the alpha below 1 where there are transparent quads, but the screen ignores it
+            .flat_map(|pixel| {
+                if bgra {
+                    [pixel[2], pixel[1], pixel[0], 255]
+                } else {
+                    [pixel[0], pixel[1], pixel[2], 255]
+                }
+            })
+            .collect();
+        buffer.unmap();
+
+        Ok(TextureData {
+            width,
+            height,
+            rgba,
+        })
+    }
+
+    pub fn stats(&self) -> RendererStats {
+        RendererStats {
+            quad_storage_buffer_capacity: self.quad_storage_buffer_capacity,
+            quad_count: self.quad_count,
+            reallocated_last_prepare: self.reallocated_last_prepare,
+            sample_count: self.sample_count,
+        }
+    }
+
+    pub fn paint<'rp>(&'rp self, render_pass: &mut wgpu::RenderPass<'rp>) {
+        // The scene was already drawn in `prepare`, the render pass is owned by egui so just copy it over the viewport
+        if let Some(scene_target) = &self.scene_target {
+            render_pass.set_pipeline(&self.blit_pipeline);
+            render_pass.set_bind_group(0, &scene_target.blit_bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Draws the quads with a camera at the origin that fits -1 to 1 on the screen, panics when there is no gpu
+    fn render(quads: &[StorageBufferQuad], size: u32) -> TextureData {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+        let adapter =
+            pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default()))
+                .expect("there is no gpu adapter to render with");
+        let (device, queue) =
+            pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor::default(), None))
+                .unwrap();
+
+        let mut renderer = Renderer::new(&device, &queue, wgpu::TextureFormat::Rgba8Unorm);
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        renderer.prepare(
+            CameraUniform {
+                position: cgmath::vec2(0.0, 0.0),
+                rotation: 0.0,
+                zoom: 1.0,
+                screen_size: cgmath::vec2(size as f32, size as f32),
+            },
+            BackgroundUniform {
+                color: cgmath::vec3(0.0, 0.0, 0.0),
+            },
+            None,
+            quads,
+            [size, size],
+            &device,
+            &queue,
+            &mut encoder,
+        );
+        queue.submit([encoder.finish()]);
+        renderer.screenshot(&device, &queue).unwrap()
+    }
+
+    // Run with `cargo test -- --ignored` on a machine with a gpu
+    #[test]
+    #[ignore = "needs a gpu adapter"]
+    fn rotated_quad_renders_as_a_diamond() {
+        let quad = StorageBufferQuad {
+            position: cgmath::vec2(0.0, 0.0),
+            scale: cgmath::vec2(1.0, 1.0),
+            color: cgmath::vec4(1.0, 1.0, 1.0, 1.0),
+            rotation: std::f32::consts::FRAC_PI_4,
+            uv_offset: cgmath::vec2(0.0, 0.0),
+            uv_size: cgmath::vec2(0.0, 0.0),
+            textured: 0,
+            circle: 0,
+        };
+        let size = 128;
+        let image = render(&[quad], size);
+        let is_drawn = |x: f32, y: f32| {
+            // -1 to 1 in the world goes across the whole screen, with y pointing up
+            let pixel_x = ((x + 1.0) * 0.5 * size as f32) as usize;
+            let pixel_y = ((1.0 - y) * 0.5 * size as f32) as usize;
+            image.rgba[(pixel_y * size as usize + pixel_x) * 4] > 128
+        };
+
+        // The corners of the diamond are on the axes, 0.707 from the center
+        for (x, y) in [(0.0, 0.0), (0.6, 0.0), (-0.6, 0.0), (0.0, 0.6), (0.0, -0.6)] {
+            assert!(is_drawn(x, y), "({x}, {y}) should be inside the diamond");
+        }
+        // Where the corners would be if it wasnt rotated
+        for (x, y) in [
+            (0.45, 0.45),
+            (-0.45, 0.45),
+            (0.45, -0.45),
+            (-0.45, -0.45),
+            (0.8, 0.0),
+        ] {
+            assert!(!is_drawn(x, y), "({x}, {y}) should be outside the diamond");
+        }
     }
 }
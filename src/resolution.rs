@@ -0,0 +1,75 @@
+// Resolves a 1D collision between two bodies moving along the same line, returning their new velocities.
+// `v1 - v2` is the closing velocity, the impulse is applied even if the bodies are separating.
+// A restitution of 0 makes the bodies move together afterwards, 1 is a perfectly elastic collision.
+// An infinite mass gives a body that is not affected by the collision.
+pub fn resolve_1d(m1: f32, v1: f32, m2: f32, v2: f32, restitution: f32) -> (f32, f32) {
+    let inverse_mass_1 = m1.recip();
+    let inverse_mass_2 = m2.recip();
+    let inverse_mass_sum = inverse_mass_1 + inverse_mass_2;
+    if inverse_mass_sum == 0.0 {
+        return (v1, v2);
+    }
+
+    let impulse = (1.0 + restitution) * (v1 - v2) / inverse_mass_sum;
+    (v1 - impulse * inverse_mass_1, v2 + impulse * inverse_mass_2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: f32, b: f32) {
+        assert!(
+            (a - b).abs() <= 1e-4 * a.abs().max(b.abs()).max(1.0),
+            "{a} != {b}"
+        );
+    }
+
+    #[test]
+    fn momentum_is_conserved() {
+        for (m1, v1, m2, v2) in [
+            (1.0, 3.0, 1.0, -1.0),
+            (1.0, 5.0, 10.0, 0.0),
+            (2.5, -1.0, 0.5, -4.0),
+        ] {
+            for restitution in [0.0, 0.3, 1.0] {
+                let (u1, u2) = resolve_1d(m1, v1, m2, v2, restitution);
+                assert_close(m1 * u1 + m2 * u2, m1 * v1 + m2 * v2);
+                // The separating velocity is the closing velocity scaled by the restitution
+                assert_close(u2 - u1, restitution * (v1 - v2));
+            }
+        }
+    }
+
+    #[test]
+    fn elastic_keeps_energy_and_inelastic_loses_it() {
+        let (m1, v1, m2, v2) = (1.0, 5.0, 10.0, -1.0);
+        let energy = |u1: f32, u2: f32| 0.5 * m1 * u1 * u1 + 0.5 * m2 * u2 * u2;
+
+        let (u1, u2) = resolve_1d(m1, v1, m2, v2, 1.0);
+        assert_close(energy(u1, u2), energy(v1, v2));
+
+        let (u1, u2) = resolve_1d(m1, v1, m2, v2, 0.0);
+        assert_close(u1, u2);
+        assert!(energy(u1, u2) < energy(v1, v2));
+    }
+
+    #[test]
+    fn equal_masses_swap_velocities_when_elastic() {
+        let (u1, u2) = resolve_1d(1.0, 2.0, 1.0, -3.0, 1.0);
+        assert_close(u1, -3.0);
+        assert_close(u2, 2.0);
+    }
+
+    #[test]
+    fn infinite_mass_is_not_moved() {
+        let (u1, u2) = resolve_1d(1.0, 4.0, f32::INFINITY, 0.0, 1.0);
+        assert_close(u1, -4.0);
+        assert_eq!(u2, 0.0);
+
+        assert_eq!(
+            resolve_1d(f32::INFINITY, 1.0, f32::INFINITY, -1.0, 0.5),
+            (1.0, -1.0)
+        );
+    }
+}
@@ -0,0 +1,45 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{Camera, Capsule, Joint, Quad, SpringJoint};
+
+// Bump this whenever the format changes so old files give an error instead of loading wrong
+pub const SCENE_VERSION: u32 = 1;
+
+// Everything needed to share a scene, the textures of the quads are indices into the textures
+// of whoever loads it, so they are not saved
+#[derive(Serialize, Deserialize)]
+pub struct Scene {
+    pub version: u32,
+    pub quads: Vec<Quad>,
+    pub capsules: Vec<Capsule>,
+    // Added without a version bump, scenes from before joints just dont have any
+    #[serde(default)]
+    pub joints: Vec<Joint>,
+    #[serde(default)]
+    pub springs: Vec<SpringJoint>,
+    pub gravity: cgmath::Vector2<f32>,
+    pub camera: Camera,
+}
+
+impl Scene {
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        std::fs::write(path, json).map_err(|e| e.to_string())
+    }
+
+    pub fn load(path: &str) -> Result<Scene, String> {
+        let json = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        // Check the version before anything else, a different version may not parse as a scene at all
+        let value = serde_json::from_str::<serde_json::Value>(&json).map_err(|e| e.to_string())?;
+        let version = value
+            .get("version")
+            .and_then(serde_json::Value::as_u64)
+            .ok_or_else(|| "the file has no scene version".to_string())?;
+        if version != SCENE_VERSION as u64 {
+            return Err(format!(
+                "the scene is version {version}, only version {SCENE_VERSION} is supported"
+            ));
+        }
+        serde_json::from_value(value).map_err(|e| e.to_string())
+    }
+}
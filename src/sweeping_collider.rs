@@ -1,12 +1,60 @@
 use cgmath::prelude::*;
 
-use crate::Collider;
+use crate::{Collider, Face, ANGULAR_SWEEP_SAMPLES};
 
+// The rotations are absolute like `Quad::rotation`, with `rotation_a` being the rotation the collider is already at
 #[derive(Clone, Copy)]
 pub struct SweepingCollider<'a, C: Collider + ?Sized> {
     pub collider: &'a C,
     pub position_a: cgmath::Vector2<f32>,
     pub position_b: cgmath::Vector2<f32>,
+    pub rotation_a: f32,
+    pub rotation_b: f32,
+}
+
+// Positive rotation is clockwise, same as quads
+fn rotate(point: cgmath::Vector2<f32>, angle: f32) -> cgmath::Vector2<f32> {
+    let (sin, cos) = (-angle).sin_cos();
+    cgmath::vec2(point.x * cos - point.y * sin, point.y * cos + point.x * sin)
+}
+
+impl<'a, C: Collider + ?Sized> SweepingCollider<'a, C> {
+    // How far through the sweep each orientation is sampled at, just the ends if it isnt rotating
+    fn sample_times(&self) -> impl Iterator<Item = f32> {
+        let samples = if self.rotation_a == self.rotation_b {
+            1
+        } else {
+            ANGULAR_SWEEP_SAMPLES
+        };
+        (0..=samples).map(move |i| i as f32 / samples as f32)
+    }
+
+    // The collider where it is at `time` through the sweep
+    pub fn at(&self, time: f32) -> PosedCollider<'_, 'a, C> {
+        PosedCollider { sweep: self, time }
+    }
+
+    // Moves a point on the collider to where it is at `time` through the sweep
+    pub fn transform(&self, point: cgmath::Vector2<f32>, time: f32) -> cgmath::Vector2<f32> {
+        let angle = (self.rotation_b - self.rotation_a) * time;
+        self.position_a.lerp(self.position_b, time) + rotate(point - self.collider.center(), angle)
+    }
+
+    // How far a point on the collider at `time` through the sweep would move over the whole sweep, if it kept
+    // moving the same way it is at that time
+    pub fn movement_at(&self, point: cgmath::Vector2<f32>, time: f32) -> cgmath::Vector2<f32> {
+        let offset = point - self.position_a.lerp(self.position_b, time);
+        (self.position_b - self.position_a)
+            + cgmath::vec2(offset.y, -offset.x) * (self.rotation_b - self.rotation_a)
+    }
+
+    // The sampled time with the furthest point in `direction`, and that point
+    fn furthest_sample(&self, direction: cgmath::Vector2<f32>) -> (f32, cgmath::Vector2<f32>) {
+        self.sample_times()
+            .map(|time| (time, self.at(time).furthest_point_in_direction(direction)))
+            .max_by(|(_, a), (_, b)| a.dot(direction).total_cmp(&b.dot(direction)))
+            .unwrap()
+    }
 }
 
 impl<'a, C: Collider + ?Sized> Collider for SweepingCollider<'a, C> {
@@ -15,17 +63,116 @@ impl<'a, C: Collider + ?Sized> Collider for SweepingCollider<'a, C> {
     }
 
     fn furthest_point_in_direction(&self, direction: cgmath::Vector2<f32>) -> cgmath::Vector2<f32> {
-        let furthest_point =
-            self.collider.furthest_point_in_direction(direction) - self.collider.center();
-        let point_a = furthest_point + self.position_a;
-        let point_b = furthest_point + self.position_b;
-
-        let distance_a = point_a.dot(direction);
-        let distance_b = point_b.dot(direction);
-        if distance_a > distance_b {
-            point_a
-        } else {
-            point_b
+        let (time, point) = self.furthest_sample(direction);
+        if self.rotation_a == self.rotation_b || direction.is_zero() {
+            return point;
+        }
+
+        // Between two samples a point swings out in an arc past the line joining them, by at most this much,
+        // so pushing the support point out by it keeps the sampled hull around the whole sweep
+        let step = (self.rotation_b - self.rotation_a).abs() / ANGULAR_SWEEP_SAMPLES as f32;
+        let radius = point.distance(self.position_a.lerp(self.position_b, time));
+        point + direction.normalize() * radius * (1.0 - (step * 0.5).cos())
+    }
+
+    // The collider's face at whichever sample of the sweep is furthest along `direction`
+    fn face_in_direction(&self, direction: cgmath::Vector2<f32>) -> Face {
+        let (time, _) = self.furthest_sample(direction);
+        self.at(time).face_in_direction(direction)
+    }
+}
+
+pub struct PosedCollider<'s, 'a, C: Collider + ?Sized> {
+    pub sweep: &'s SweepingCollider<'a, C>,
+    pub time: f32,
+}
+
+impl<'s, 'a, C: Collider + ?Sized> Collider for PosedCollider<'s, 'a, C> {
+    fn center(&self) -> cgmath::Vector2<f32> {
+        self.sweep
+            .transform(self.sweep.collider.center(), self.time)
+    }
+
+    fn furthest_point_in_direction(&self, direction: cgmath::Vector2<f32>) -> cgmath::Vector2<f32> {
+        let angle = (self.sweep.rotation_b - self.sweep.rotation_a) * self.time;
+        let point = self
+            .sweep
+            .collider
+            .furthest_point_in_direction(rotate(direction, -angle));
+        self.sweep.transform(point, self.time)
+    }
+
+    fn face_in_direction(&self, direction: cgmath::Vector2<f32>) -> Face {
+        let angle = (self.sweep.rotation_b - self.sweep.rotation_a) * self.time;
+        let face = self
+            .sweep
+            .collider
+            .face_in_direction(rotate(direction, -angle));
+        Face {
+            a: self.sweep.transform(face.a, self.time),
+            b: self.sweep.transform(face.b, self.time),
+            normal: rotate(face.normal, angle),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Quad;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn support_covers_every_intermediate_pose() {
+        const POSES: usize = 1000;
+        for seed in 0..100 {
+            let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+            let quad = Quad {
+                position: cgmath::vec2(rng.gen_range(-5.0..5.0), rng.gen_range(-5.0..5.0)),
+                rotation: rng.gen_range(-3.2..3.2),
+                scale: cgmath::vec2(rng.gen_range(0.1..3.0), rng.gen_range(0.1..3.0)),
+                ..Quad::default()
+            };
+            let sweep = SweepingCollider {
+                collider: &quad,
+                position_a: quad.position,
+                position_b: quad.position
+                    + cgmath::vec2(rng.gen_range(-5.0..5.0), rng.gen_range(-5.0..5.0)),
+                rotation_a: quad.rotation,
+                rotation_b: quad.rotation
+                    + if rng.gen_bool(0.2) {
+                        0.0
+                    } else {
+                        rng.gen_range(-4.0..4.0)
+                    },
+            };
+
+            for _ in 0..20 {
+                let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+                let direction = cgmath::vec2(angle.cos(), angle.sin());
+                let sampled = (0..=POSES)
+                    .map(|i| {
+                        let time = i as f32 / POSES as f32;
+                        let pose = Quad {
+                            position: sweep.position_a.lerp(sweep.position_b, time),
+                            rotation: sweep.rotation_a
+                                + (sweep.rotation_b - sweep.rotation_a) * time,
+                            ..quad
+                        };
+                        pose.furthest_point_in_direction(direction).dot(direction)
+                    })
+                    .fold(f32::NEG_INFINITY, f32::max);
+                let support = sweep.furthest_point_in_direction(direction).dot(direction);
+                // It can stick out a bit past the sweep, but must never cut into it
+                assert!(
+                    support >= sampled - 0.001,
+                    "seed {seed}: support {support} is inside the sampled sweep {sampled}"
+                );
+                assert!(
+                    support <= sampled + 0.05 * quad.scale.magnitude(),
+                    "seed {seed}: support {support} is too far past the sampled sweep {sampled}"
+                );
+            }
         }
     }
 }
@@ -0,0 +1,2201 @@
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+
+use arrayvec::ArrayVec;
+use cgmath::prelude::*;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    distance_between, get_collision, get_speculative_collision, get_swept_collision, resolve_1d,
+    Aabb, BodyType, Broadphase, Capsule, Collider, Collision, CollisionParams, Joint, Quad,
+    SpringJoint, SweepingCollider, FLASH_DURATION, IMPULSE_SLOP, JITTER_ANGLE,
+    MAX_PHYSICS_ITERATIONS, PENETRATION_SLOP, WARM_START_FACTOR,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SweepingMode {
+    Disabled,
+    All,
+    // Only sweep dynamic quads against static ones, which is where most tunneling happens
+    DynamicVsStatic,
+    // Only sweep pairs where one of the quads moves further than a fraction of its size in one step,
+    // which is when it can tunnel through things
+    Automatic,
+    // Dont sweep anything, instead quads that are about to touch get a contact with the gap between them
+    // that stops them right at the surface, which is a lot cheaper than building swept hulls
+    Speculative,
+}
+
+impl SweepingMode {
+    pub const ALL: [SweepingMode; 5] = [
+        Self::Disabled,
+        Self::All,
+        Self::DynamicVsStatic,
+        Self::Automatic,
+        Self::Speculative,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Disabled => "Disabled",
+            Self::All => "All",
+            Self::DynamicVsStatic => "Dynamic vs Static",
+            Self::Automatic => "Automatic",
+            Self::Speculative => "Speculative",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GravityAxes {
+    Both,
+    XOnly,
+    YOnly,
+}
+
+impl GravityAxes {
+    pub fn apply(self, gravity: cgmath::Vector2<f32>) -> cgmath::Vector2<f32> {
+        match self {
+            Self::Both => gravity,
+            Self::XOnly => cgmath::vec2(gravity.x, 0.0),
+            Self::YOnly => cgmath::vec2(0.0, gravity.y),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GravityIntegration {
+    // Integrate gravity first so the solver can cancel it out in the same step
+    BeforeSolve,
+    // Integrate gravity after solving, so contacts only see it on the next step
+    AfterSolve,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ContactResolution {
+    // Resolve every contact of a quad at once each iteration
+    Simultaneous,
+    // Only resolve the deepest contact of a quad each iteration, slower to converge but the contacts dont fight
+    DeepestFirst,
+}
+
+// The other quad's index, and the accumulated normal and tangent impulse of each contact point
+pub type ContactImpulses = (usize, [f32; 2], [f32; 2]);
+
+// What happened in a single step, for noticing when a scene becomes unstable
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StepReport {
+    // The most iterations any island needed
+    pub iterations: usize,
+    pub hit_iteration_cap: bool,
+    // The number of pairs of quads that were touching
+    pub contact_count: usize,
+    // How many times GJK or EPA gave up during the step
+    pub collision_iteration_cap_hits: usize,
+}
+
+// A pair of quads that were touching during a step, `a` is the smaller index and the normal points from `a` to `b`
+#[derive(Debug, Clone)]
+pub struct ContactEvent {
+    pub a: usize,
+    pub b: usize,
+    pub normal: cgmath::Vector2<f32>,
+    // Negative for quads that were only just touching, by how far apart they were
+    pub depth: f32,
+    // In world space, the same as `Collision::contact_points`
+    pub contact_points: ArrayVec<cgmath::Vector2<f32>, 2>,
+}
+
+pub type ContactCallback = Box<dyn FnMut(&ContactEvent)>;
+
+type ContactBody<'a> = (
+    f32,
+    &'a mut cgmath::Vector2<f32>,
+    &'a mut cgmath::Vector2<f32>,
+);
+
+// Pushes two bodies out of each other along the collision normal, split by their inverse masses,
+// and stops them moving into each other. Each body is its inverse mass, position and velocity
+fn resolve_contact(mut collision: Collision, a: ContactBody<'_>, b: ContactBody<'_>) {
+    let (inverse_mass_a, position_a, velocity_a) = a;
+    let (inverse_mass_b, position_b, velocity_b) = b;
+    let inverse_mass_sum = inverse_mass_a + inverse_mass_b;
+    if inverse_mass_sum == 0.0 {
+        return;
+    }
+
+    collision.orient_towards(*position_b - *position_a);
+    let normal = collision.normal;
+    *position_a -= normal * collision.depth * (inverse_mass_a / inverse_mass_sum);
+    *position_b += normal * collision.depth * (inverse_mass_b / inverse_mass_sum);
+
+    // They are allowed to move apart, so only closing velocities are resolved
+    let (normal_velocity_a, normal_velocity_b) = (velocity_a.dot(normal), velocity_b.dot(normal));
+    if normal_velocity_a > normal_velocity_b {
+        let (new_velocity_a, new_velocity_b) = resolve_1d(
+            inverse_mass_a.recip(),
+            normal_velocity_a,
+            inverse_mass_b.recip(),
+            normal_velocity_b,
+            0.0,
+        );
+        *velocity_a += normal * (new_velocity_a - normal_velocity_a);
+        *velocity_b += normal * (new_velocity_b - normal_velocity_b);
+    }
+}
+
+// The aabb covers everywhere the quad could be swept to, in case its velocity gets reversed,
+// and how far its corners could swing if its spinning, which is never past the circle around it
+fn swept_aabb(quad: &Quad, ts: f32) -> Aabb {
+    let aabb = quad.aabb();
+    let radius = quad.scale.magnitude() * 0.5;
+    let swing = (quad.angular_velocity.abs() * ts).min(1.0) * radius;
+    let movement = cgmath::vec2(1.0, 1.0) * (quad.velocity.magnitude() * ts + swing);
+    Aabb {
+        min: aabb.min - movement,
+        max: aabb.max + movement,
+    }
+}
+
+// The z component of the 3d cross product
+fn cross(a: cgmath::Vector2<f32>, b: cgmath::Vector2<f32>) -> f32 {
+    a.x * b.y - a.y * b.x
+}
+
+// The collision between two quads with the normal pointing from `quad` to `other`. Quads with the same center have
+// no direction between them to check the normal against, and identical ones find the same normal from both sides,
+// which would push them the same way forever. So the pair uses the collision from the side of the smaller index
+fn quad_collision(
+    quad: &Quad,
+    index: usize,
+    other: &Quad,
+    other_index: usize,
+    params: &CollisionParams,
+    cap_hits: &mut usize,
+) -> Option<Collision> {
+    if quad.position == other.position && index > other_index {
+        let mut collision = get_collision(other, quad, params, cap_hits)?;
+        collision.normal = -collision.normal;
+        Some(collision)
+    } else {
+        let mut collision = get_collision(quad, other, params, cap_hits)?;
+        // For deep overlaps EPA can return a normal pointing away from the other quad, which would make the solver
+        // accelerate the quads into each other
+        collision.orient_towards(other.position - quad.position);
+        Some(collision)
+    }
+}
+
+// How a body moves this step, and whether it was going through or resting on a one way platform last step
+struct OneWayPair {
+    velocity: cgmath::Vector2<f32>,
+    // The time step of the body
+    ts: f32,
+    was_passing: bool,
+    was_touching: bool,
+}
+
+// Whether `body` goes through the one way `platform` this step instead of being pushed by it
+fn passes_one_way<C: Collider + ?Sized>(
+    platform: &Quad,
+    body: &C,
+    pair: OneWayPair,
+    params: &CollisionParams,
+    cap_hits: &mut usize,
+) -> bool {
+    let OneWayPair {
+        velocity,
+        ts,
+        was_passing,
+        was_touching,
+    } = pair;
+    let normal = platform.one_way_normal;
+    match get_collision(platform, body, params, cap_hits) {
+        // Quads keep going through until they come out, so a quad that starts inside isnt suddenly pushed out
+        Some(_) if was_passing => true,
+        // Quads resting on the platform stay on it
+        Some(_) if was_touching => false,
+        Some(mut collision) => {
+            collision.orient_towards(body.center() - platform.position);
+            // A new overlap only lands on the platform if the body could have come in from the allowed side since
+            // the last step, anything deeper was already inside
+            let closing_speed = -(velocity - platform.velocity).dot(normal);
+            !(collision.normal.dot(normal) > 0.0
+                && closing_speed > 0.0
+                && collision.depth <= closing_speed * ts + params.penetration_slop)
+        }
+        None if was_touching => false,
+        None => match distance_between(platform, body, params, cap_hits) {
+            Some((distance, closest_platform, closest_body)) => {
+                let direction = if distance > 0.0 {
+                    closest_body - closest_platform
+                } else {
+                    body.center() - platform.position
+                };
+                direction.dot(normal) <= 0.0
+            }
+            None => was_passing,
+        },
+    }
+}
+
+// Deterministically maps the inputs to a value in [-1, 1], using the splitmix64 finalizer
+fn jitter(seed: u64, step: u64, a: usize, b: usize) -> f32 {
+    let mut x = seed
+        ^ step.wrapping_mul(0x9E3779B97F4A7C15)
+        ^ (a as u64).wrapping_mul(0xBF58476D1CE4E5B9)
+        ^ (b as u64).wrapping_mul(0x94D049BB133111EB);
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D049BB133111EB);
+    x ^= x >> 31;
+    (x >> 40) as f32 / (1u64 << 23) as f32 - 1.0
+}
+
+// Everything that is simulated, and the settings for how, without anything to do with showing it.
+// `App` drives one of these from its fixed updates
+#[derive(Serialize, Deserialize)]
+pub struct PhysicsWorld {
+    pub gravity: cgmath::Vector2<f32>,
+    pub gravity_axes: GravityAxes,
+    pub gravity_integration: GravityIntegration,
+    pub sweeping_mode: SweepingMode,
+    pub sweeping_threshold: f32,
+    pub relaxation: f32,
+    pub contact_resolution: ContactResolution,
+    pub broadphase_cell_size: f32,
+    pub collision_params: CollisionParams,
+    pub ignore_internal_edges: bool,
+    pub velocity_scale: f32,
+    pub continuous_velocity_scale: bool,
+    // How much of its velocity and angular velocity a quad loses per second, like moving through air
+    pub linear_damping: f32,
+    pub angular_damping: f32,
+    pub jitter_enabled: bool,
+    pub jitter_seed: u64,
+    // Start each contact from the impulses it ended the last step with, so resting contacts dont have to build back up
+    pub warm_starting: bool,
+    pub lod_enabled: bool,
+    pub lod_distance: f32,
+    pub lod_speed: f32,
+    pub lod_interval: u64,
+    pub sleeping_enabled: bool,
+    pub sleep_threshold: f32,
+    pub sleep_steps: u32,
+    pub quads: Vec<Quad>,
+    pub capsules: Vec<Capsule>,
+    pub joints: Vec<Joint>,
+    pub springs: Vec<SpringJoint>,
+    // Where the lod distance is measured from, the app keeps it on the camera
+    pub lod_center: cgmath::Vector2<f32>,
+    // Whether to keep the collisions of each step around for drawing them, off by default to save the copies
+    #[serde(skip)]
+    pub record_collisions: bool,
+    #[serde(skip)]
+    step_count: u64,
+    #[serde(skip)]
+    old_quads: Vec<Quad>,
+    #[serde(skip)]
+    broadphase: Broadphase,
+    // How much time each quad is advanced by this step, 0 for quads that are skipped by the lod
+    #[serde(skip)]
+    time_steps: Vec<f32>,
+    // The accumulated impulses of every contact this step
+    #[serde(skip)]
+    contact_impulses: Vec<Vec<ContactImpulses>>,
+    // The contact impulses from the step before, that new contacts are warm started from
+    #[serde(skip)]
+    old_contact_impulses: Vec<Vec<ContactImpulses>>,
+    // The collisions each quad found with the quads around it at the start of the last step,
+    // only filled in while `record_collisions` is on
+    #[serde(skip)]
+    collisions: Vec<Vec<Collision>>,
+    // How deep each contact of a quad is allowed to still be overlapping at the end of this step, keyed by the other
+    // quad's index, so deep overlaps are pushed out over several steps
+    #[serde(skip)]
+    allowed_penetrations: Vec<Vec<(usize, f32)>>,
+    // How many consecutive steps each pair of quads has been in contact, keyed by the sorted pair of indices
+    #[serde(skip)]
+    contact_persistence: HashMap<(usize, usize), u32>,
+    // The sorted pairs of quads that overlap where at least one of them is a sensor, from the last step
+    #[serde(skip)]
+    trigger_events: Vec<(usize, usize)>,
+    // The first collision each quad found with each quad it was in contact with this step, or touching at the start of
+    // it, keyed by the other quad's index
+    #[serde(skip)]
+    contacts: Vec<Vec<(usize, Collision)>>,
+    #[serde(skip)]
+    contact_callbacks: Vec<ContactCallback>,
+    // The sorted pairs with a one way quad that pass through each other this step instead of colliding
+    #[serde(skip)]
+    one_way_passes: Vec<(usize, usize)>,
+    // The same for capsules, as sorted (capsule, quad) pairs, and the pairs that were touching last step
+    #[serde(skip)]
+    capsule_one_way_passes: Vec<(usize, usize)>,
+    #[serde(skip)]
+    capsule_contacts: Vec<(usize, usize)>,
+    // The island each quad was solved in last step, `None` for quads that arent solved like static ones
+    #[serde(skip)]
+    islands: Vec<Option<usize>>,
+    // How many iterations each island took to be solved last step
+    #[serde(skip)]
+    island_iterations: Vec<usize>,
+    #[serde(skip)]
+    last_step_report: StepReport,
+}
+
+impl Default for PhysicsWorld {
+    fn default() -> Self {
+        Self {
+            gravity: cgmath::vec2(0.0, -9.81),
+            gravity_axes: GravityAxes::Both,
+            gravity_integration: GravityIntegration::BeforeSolve,
+            sweeping_mode: SweepingMode::Disabled,
+            sweeping_threshold: 0.5,
+            relaxation: 1.0,
+            contact_resolution: ContactResolution::Simultaneous,
+            broadphase_cell_size: 2.0,
+            collision_params: CollisionParams::default(),
+            ignore_internal_edges: true,
+            velocity_scale: 1.0,
+            continuous_velocity_scale: false,
+            linear_damping: 0.0,
+            angular_damping: 0.0,
+            jitter_enabled: false,
+            jitter_seed: 0,
+            warm_starting: false,
+            lod_enabled: false,
+            lod_distance: 50.0,
+            lod_speed: 0.1,
+            lod_interval: 4,
+            sleeping_enabled: false,
+            sleep_threshold: 0.5,
+            sleep_steps: 100,
+            quads: vec![],
+            capsules: vec![],
+            joints: vec![],
+            springs: vec![],
+            lod_center: cgmath::vec2(0.0, 0.0),
+            record_collisions: false,
+            step_count: 0,
+            old_quads: vec![],
+            broadphase: Broadphase::default(),
+            time_steps: vec![],
+            contact_impulses: vec![],
+            old_contact_impulses: vec![],
+            collisions: vec![],
+            allowed_penetrations: vec![],
+            contact_persistence: HashMap::new(),
+            trigger_events: vec![],
+            contacts: vec![],
+            contact_callbacks: vec![],
+            one_way_passes: vec![],
+            capsule_one_way_passes: vec![],
+            capsule_contacts: vec![],
+            islands: vec![],
+            island_iterations: vec![],
+            last_step_report: StepReport::default(),
+        }
+    }
+}
+
+impl PhysicsWorld {
+    pub fn new(quads: Vec<Quad>) -> Self {
+        Self {
+            quads,
+            ..Default::default()
+        }
+    }
+
+    // Returns the index of the new quad
+    pub fn add_quad(&mut self, quad: Quad) -> usize {
+        self.quads.push(quad);
+        self.quads.len() - 1
+    }
+
+    // Removes the quad along with the joints and springs attached to it, the quads after it move down an index
+    pub fn remove_quad(&mut self, index: usize) -> Quad {
+        let quad = self.quads.remove(index);
+        // A joint with only one quad left has nothing to hold
+        self.joints
+            .retain(|joint| joint.body_a != index && joint.body_b != index);
+        for joint in &mut self.joints {
+            joint.body_a -= (joint.body_a > index) as usize;
+            joint.body_b -= (joint.body_b > index) as usize;
+        }
+        self.springs
+            .retain(|spring| spring.body_a != index && spring.body_b != index);
+        for spring in &mut self.springs {
+            spring.body_a -= (spring.body_a > index) as usize;
+            spring.body_b -= (spring.body_b > index) as usize;
+        }
+        // The indices after the removed quad shifted, so the pairs would refer to the wrong quads
+        self.clear_contacts();
+        quad
+    }
+
+    // Forgets everything from the last step that refers to quads by index, for when the quads are replaced
+    pub fn clear_contacts(&mut self) {
+        self.old_quads.clear();
+        self.contact_impulses.clear();
+        self.old_contact_impulses.clear();
+        self.contact_persistence.clear();
+        self.trigger_events.clear();
+        self.contacts.clear();
+        self.one_way_passes.clear();
+        self.capsule_one_way_passes.clear();
+        self.capsule_contacts.clear();
+    }
+
+    // How many times `step` has been called
+    pub fn step_count(&self) -> u64 {
+        self.step_count
+    }
+
+    // The accumulated impulses of every contact of each quad from the last step
+    pub fn contact_impulses(&self) -> &[Vec<ContactImpulses>] {
+        &self.contact_impulses
+    }
+
+    // Empty for every quad unless `record_collisions` was on for the last step
+    pub fn collisions(&self) -> &[Vec<Collision>] {
+        &self.collisions
+    }
+
+    // The sorted pairs of quads that overlapped a sensor in the last step
+    pub fn trigger_events(&self) -> &[(usize, usize)] {
+        &self.trigger_events
+    }
+
+    // Called with every contact event at the end of each step. The solve runs in parallel and finds each contact many
+    // times, so the callbacks are only called once it is done and nothing is changing the quads
+    pub fn on_contact(&mut self, callback: ContactCallback) {
+        self.contact_callbacks.push(callback);
+    }
+
+    pub fn last_step_report(&self) -> StepReport {
+        self.last_step_report
+    }
+
+    // How many iterations each group of touching quads took to be solved in the last step
+    pub fn island_iterations(&self) -> &[usize] {
+        &self.island_iterations
+    }
+
+    // A rough estimate of the memory used by the simulation on the cpu, going by the capacity of its buffers
+    pub fn estimated_memory(&self) -> usize {
+        let quads =
+            (self.quads.capacity() + self.old_quads.capacity()) * std::mem::size_of::<Quad>();
+        let contact_impulses = [&self.contact_impulses, &self.old_contact_impulses]
+            .into_iter()
+            .map(|contact_impulses| {
+                contact_impulses.capacity() * std::mem::size_of::<Vec<ContactImpulses>>()
+                    + contact_impulses
+                        .iter()
+                        .map(|impulses| {
+                            impulses.capacity() * std::mem::size_of::<ContactImpulses>()
+                        })
+                        .sum::<usize>()
+            })
+            .sum::<usize>();
+        let contact_persistence =
+            self.contact_persistence.capacity() * std::mem::size_of::<((usize, usize), u32)>();
+        let time_steps = self.time_steps.capacity() * std::mem::size_of::<f32>();
+        quads + contact_impulses + contact_persistence + time_steps
+    }
+
+    // The number of consecutive steps the two quads have been touching for, 0 if they are not touching
+    pub fn contact_persistence(&self, a: usize, b: usize) -> u32 {
+        self.contact_persistence
+            .get(&(a.min(b), a.max(b)))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    // Pushes overlapping quads apart without touching their velocities, for cleaning up a scene before simulating it
+    pub fn separate_overlaps(quads: &mut [Quad], params: &CollisionParams) {
+        for _ in 0..MAX_PHYSICS_ITERATIONS {
+            let deltas = quads
+                .par_iter()
+                .enumerate()
+                .map(|(index, quad)| {
+                    let mut position_delta = cgmath::vec2(0.0, 0.0);
+                    if quad.body_type == BodyType::Dynamic {
+                        for (other_index, other) in quads.iter().enumerate() {
+                            if other_index != index
+                                && quad.collides_with(other)
+                                && !quad.is_sensor
+                                && !other.is_sensor
+                            {
+                                if let Some(collision) =
+                                    quad_collision(quad, index, other, other_index, params, &mut 0)
+                                {
+                                    let inverse_mass = quad.inverse_mass();
+                                    position_delta -= collision.normal
+                                        * collision.depth
+                                        * (inverse_mass / (inverse_mass + other.inverse_mass()));
+                                }
+                            }
+                        }
+                    }
+                    position_delta
+                })
+                .collect::<Vec<_>>();
+
+            if deltas.iter().all(|delta| delta.is_zero()) {
+                break;
+            }
+            for (quad, delta) in quads.iter_mut().zip(deltas) {
+                quad.position += delta;
+            }
+        }
+    }
+
+    // Whether pushing a quad out of the static quad at `static_index` along `-normal` would push
+    // it into a neighbouring static quad, meaning the contact is on a seam between tiles
+    fn is_internal_edge(quads: &[Quad], static_index: usize, normal: cgmath::Vector2<f32>) -> bool {
+        let tile = &quads[static_index];
+        let half_extent = (tile.furthest_point_in_direction(-normal) - tile.position).dot(-normal);
+        let beyond_face = tile.position - normal * (half_extent + PENETRATION_SLOP);
+        quads.iter().enumerate().any(|(index, quad)| {
+            index != static_index
+                && quad.body_type == BodyType::Static
+                && quad.contains_point(beyond_face)
+        })
+    }
+
+    fn update_trigger_events(&mut self, cap_hits: &AtomicUsize) {
+        self.trigger_events.clear();
+        if !self.quads.iter().any(|quad| quad.is_sensor) {
+            return;
+        }
+
+        self.broadphase.build(
+            self.broadphase_cell_size,
+            self.quads.iter().map(|quad| quad.aabb()),
+        );
+        let (quads, params) = (&self.quads, &self.collision_params);
+        let trigger_events = quads
+            .par_iter()
+            .enumerate()
+            .flat_map_iter(|(index, quad)| {
+                self.broadphase
+                    .query(quad.aabb())
+                    .into_iter()
+                    .filter(move |&other_index| {
+                        let other = &quads[other_index];
+                        other_index > index
+                            && (quad.is_sensor || other.is_sensor)
+                            && quad.collides_with(other)
+                            && {
+                                let mut hits = 0;
+                                let touching =
+                                    get_collision(quad, other, params, &mut hits).is_some();
+                                cap_hits.fetch_add(hits, Ordering::Relaxed);
+                                touching
+                            }
+                    })
+                    .map(move |other_index| (index, other_index))
+            })
+            .collect::<Vec<_>>();
+        self.trigger_events = trigger_events;
+    }
+
+    // Both quads of a pair solve their contact from their own side, so whether they pass through a one way quad has
+    // to be decided once for the pair before solving
+    fn update_one_way_passes(&mut self, cap_hits: &AtomicUsize) {
+        let old_passes = std::mem::take(&mut self.one_way_passes);
+        if !self.quads.iter().any(|quad| quad.one_way) {
+            return;
+        }
+
+        self.broadphase.build(
+            self.broadphase_cell_size,
+            self.quads
+                .iter()
+                .zip(&self.time_steps)
+                .map(|(quad, &ts)| swept_aabb(quad, ts)),
+        );
+        let (quads, time_steps, params) = (&self.quads, &self.time_steps, &self.collision_params);
+        let (old_passes, contact_persistence) = (&old_passes, &self.contact_persistence);
+        let one_way_passes = quads
+            .par_iter()
+            .enumerate()
+            .flat_map_iter(|(index, quad)| {
+                self.broadphase
+                    .query(swept_aabb(quad, time_steps[index]))
+                    .into_iter()
+                    .filter(move |&other_index| {
+                        let other = &quads[other_index];
+                        if other_index <= index
+                            || !(quad.one_way || other.one_way)
+                            || !quad.collides_with(other)
+                        {
+                            return false;
+                        }
+                        let was_passing = old_passes.binary_search(&(index, other_index)).is_ok();
+                        let was_touching = contact_persistence.contains_key(&(index, other_index));
+                        let mut hits = 0;
+                        let mut passes = |platform: &Quad, quad: &Quad, ts: f32| {
+                            platform.one_way
+                                && passes_one_way(
+                                    platform,
+                                    quad,
+                                    OneWayPair {
+                                        velocity: quad.velocity,
+                                        ts,
+                                        was_passing,
+                                        was_touching,
+                                    },
+                                    params,
+                                    &mut hits,
+                                )
+                        };
+                        let passes = passes(quad, other, time_steps[other_index])
+                            || passes(other, quad, time_steps[index]);
+                        cap_hits.fetch_add(hits, Ordering::Relaxed);
+                        passes
+                    })
+                    .map(move |other_index| (index, other_index))
+            })
+            .collect::<Vec<_>>();
+        self.one_way_passes = one_way_passes;
+        // Sorted so the solver can binary search it
+        self.one_way_passes.sort_unstable();
+    }
+
+    // Groups the quads that can touch each other this step, through contacts or joints, so each group can stop
+    // iterating as soon as it is solved. Only quads that are solved are put in an island, static ones dont join
+    // the quads on them together
+    fn update_islands(&mut self) {
+        self.broadphase.build(
+            self.broadphase_cell_size,
+            self.quads
+                .iter()
+                .zip(&self.time_steps)
+                .map(|(quad, &ts)| swept_aabb(quad, ts)),
+        );
+
+        let solvable = |index: usize| {
+            self.quads[index].body_type == BodyType::Dynamic && self.time_steps[index] > 0.0
+        };
+        // Union find where the root of each set is its lowest index
+        fn find(parents: &mut [usize], mut index: usize) -> usize {
+            while parents[index] != index {
+                parents[index] = parents[parents[index]];
+                index = parents[index];
+            }
+            index
+        }
+        fn union(parents: &mut [usize], a: usize, b: usize) {
+            let (a, b) = (find(parents, a), find(parents, b));
+            parents[a.max(b)] = a.min(b);
+        }
+
+        let mut parents = (0..self.quads.len()).collect::<Vec<_>>();
+        let mut nearby = vec![];
+        for (index, quad) in self.quads.iter().enumerate() {
+            if !solvable(index) {
+                continue;
+            }
+            self.broadphase
+                .query_into(swept_aabb(quad, self.time_steps[index]), &mut nearby);
+            for &other_index in &nearby {
+                let other = &self.quads[other_index];
+                if solvable(other_index)
+                    && quad.collides_with(other)
+                    && !quad.is_sensor
+                    && !other.is_sensor
+                {
+                    union(&mut parents, index, other_index);
+                }
+            }
+        }
+        for joint in &self.joints {
+            if joint.body_a < self.quads.len()
+                && joint.body_b < self.quads.len()
+                && solvable(joint.body_a)
+                && solvable(joint.body_b)
+            {
+                union(&mut parents, joint.body_a, joint.body_b);
+            }
+        }
+
+        // The root is the lowest index, so it is always numbered before the rest of its island
+        self.islands.clear();
+        self.island_iterations.clear();
+        for index in 0..self.quads.len() {
+            let island = solvable(index).then(|| {
+                let root = find(&mut parents, index);
+                if root == index {
+                    self.island_iterations.push(0);
+                    self.island_iterations.len() - 1
+                } else {
+                    self.islands[root].unwrap()
+                }
+            });
+            self.islands.push(island);
+        }
+    }
+
+    // Capsules are resolved one pair at a time after the quads, only along the normal so contacts never spin them
+    fn solve_capsules(&mut self, ts: f32, cap_hits: &mut usize) {
+        if self.capsules.is_empty() {
+            return;
+        }
+
+        let gravity = self.gravity_axes.apply(self.gravity);
+        let (linear_damping, angular_damping) = (self.linear_damping, self.angular_damping);
+        for capsule in self
+            .capsules
+            .iter_mut()
+            .filter(|capsule| capsule.body_type == BodyType::Dynamic)
+        {
+            capsule.velocity += gravity * capsule.gravity_scale * ts;
+            let linear_damping = capsule.linear_damping.unwrap_or(linear_damping);
+            capsule.velocity *= (1.0 - linear_damping * ts).max(0.0);
+            let angular_damping = capsule.angular_damping.unwrap_or(angular_damping);
+            capsule.angular_velocity *= (1.0 - angular_damping * ts).max(0.0);
+        }
+
+        // Quads come first in the grid, then the capsules
+        let quad_count = self.quads.len();
+        let build_grid = |broadphase: &mut Broadphase, quads: &[Quad], capsules: &[Capsule]| {
+            broadphase.build(
+                self.broadphase_cell_size,
+                quads
+                    .iter()
+                    .map(Collider::aabb)
+                    .chain(capsules.iter().map(Collider::aabb)),
+            );
+        };
+        build_grid(&mut self.broadphase, &self.quads, &self.capsules);
+
+        // Decided once for the whole step, like for quads
+        let old_passes = std::mem::take(&mut self.capsule_one_way_passes);
+        let mut nearby = vec![];
+        for (index, capsule) in self.capsules.iter().enumerate() {
+            self.broadphase.query_into(capsule.aabb(), &mut nearby);
+            for &quad_index in nearby
+                .iter()
+                .filter(|&&other_index| other_index < quad_count)
+            {
+                let quad = &self.quads[quad_index];
+                let pair = (index, quad_index);
+                if quad.one_way
+                    && capsule.collides_with_quad(quad)
+                    && passes_one_way(
+                        quad,
+                        capsule,
+                        OneWayPair {
+                            velocity: capsule.velocity,
+                            ts,
+                            was_passing: old_passes.binary_search(&pair).is_ok(),
+                            was_touching: self.capsule_contacts.binary_search(&pair).is_ok(),
+                        },
+                        &self.collision_params,
+                        cap_hits,
+                    )
+                {
+                    self.capsule_one_way_passes.push(pair);
+                }
+            }
+        }
+
+        let mut contacts = vec![];
+        for iteration in 0..MAX_PHYSICS_ITERATIONS {
+            if iteration > 0 {
+                build_grid(&mut self.broadphase, &self.quads, &self.capsules);
+            }
+            let mut solved = true;
+            for index in 0..self.capsules.len() {
+                self.broadphase
+                    .query_into(self.capsules[index].aabb(), &mut nearby);
+                let (capsules, others) = self.capsules.split_at_mut(index + 1);
+                let capsule = &mut capsules[index];
+                for &other_index in &nearby {
+                    let Some(quad) = self.quads.get_mut(other_index) else {
+                        // Each pair of capsules is only solved by the first one
+                        let Some(other) = (other_index - quad_count)
+                            .checked_sub(index + 1)
+                            .and_then(|other_index| others.get_mut(other_index))
+                        else {
+                            continue;
+                        };
+                        if !capsule.collides_with(other)
+                            || (capsule.body_type != BodyType::Dynamic
+                                && other.body_type != BodyType::Dynamic)
+                        {
+                            continue;
+                        }
+                        if let Some(collision) =
+                            get_collision(&*capsule, &*other, &self.collision_params, cap_hits)
+                        {
+                            solved = false;
+                            resolve_contact(
+                                collision,
+                                (
+                                    capsule.inverse_mass(),
+                                    &mut capsule.position,
+                                    &mut capsule.velocity,
+                                ),
+                                (
+                                    other.inverse_mass(),
+                                    &mut other.position,
+                                    &mut other.velocity,
+                                ),
+                            );
+                        }
+                        continue;
+                    };
+                    if quad.is_sensor
+                        || !capsule.collides_with_quad(quad)
+                        || (capsule.body_type != BodyType::Dynamic
+                            && quad.body_type != BodyType::Dynamic)
+                        || self
+                            .capsule_one_way_passes
+                            .binary_search(&(index, other_index))
+                            .is_ok()
+                    {
+                        continue;
+                    }
+                    if let Some(collision) =
+                        get_collision(&*capsule, &*quad, &self.collision_params, cap_hits)
+                    {
+                        solved = false;
+                        contacts.push((index, other_index));
+                        resolve_contact(
+                            collision,
+                            (
+                                capsule.inverse_mass(),
+                                &mut capsule.position,
+                                &mut capsule.velocity,
+                            ),
+                            (quad.inverse_mass(), &mut quad.position, &mut quad.velocity),
+                        );
+                        quad.wake();
+                    }
+                }
+            }
+            if solved {
+                break;
+            }
+        }
+        contacts.sort_unstable();
+        contacts.dedup();
+        self.capsule_contacts = contacts;
+
+        for capsule in self
+            .capsules
+            .iter_mut()
+            .filter(|capsule| capsule.body_type != BodyType::Static)
+        {
+            capsule.position += capsule.velocity * ts;
+            capsule.rotation += capsule.angular_velocity * ts;
+        }
+    }
+
+    // Sleeping quads are woken up when an awake quad moves into them
+    fn wake_quads(&mut self, ts: f32) {
+        let sleep_threshold = self.sleep_threshold;
+        self.broadphase.build(
+            self.broadphase_cell_size,
+            self.quads.iter().map(|quad| swept_aabb(quad, ts)),
+        );
+        let woken = self
+            .quads
+            .par_iter()
+            .map(|quad| {
+                quad.is_sleeping
+                    && self
+                        .broadphase
+                        .query(quad.aabb())
+                        .into_iter()
+                        .any(|other_index| {
+                            let other = &self.quads[other_index];
+                            other.body_type != BodyType::Static
+                                && !other.is_sleeping
+                                && !other.is_sensor
+                                && other.collides_with(quad)
+                                && other.is_moving(sleep_threshold)
+                                && swept_aabb(other, ts).intersects(&quad.aabb())
+                        })
+            })
+            .collect::<Vec<_>>();
+        for (quad, woken) in self.quads.iter_mut().zip(woken) {
+            if woken {
+                quad.wake();
+            }
+        }
+    }
+
+    // Quads that are far from the camera and barely moving are only stepped every `lod_interval` fixed updates,
+    // with a bigger time step to make up for it. They go back to full rate as soon as they start moving
+    fn update_time_steps(&mut self, ts: f32) {
+        let step = self.step_count;
+        let camera_position = self.lod_center;
+        let (lod_enabled, lod_distance, lod_speed) =
+            (self.lod_enabled, self.lod_distance, self.lod_speed);
+        let lod_interval = self.lod_interval.max(1);
+        let sleeping_enabled = self.sleeping_enabled;
+        self.quads
+            .par_iter()
+            .enumerate()
+            .map(|(index, quad)| {
+                let low_rate = lod_enabled
+                    && quad.position.distance(camera_position) > lod_distance
+                    && quad.velocity.magnitude() < lod_speed
+                    && quad.angular_velocity.abs() < lod_speed;
+                if sleeping_enabled && quad.is_sleeping {
+                    0.0
+                } else if !low_rate {
+                    ts
+                } else if (step + index as u64).is_multiple_of(lod_interval) {
+                    // Offset by the index so the low rate quads dont all get stepped on the same fixed update
+                    ts * lod_interval as f32
+                } else {
+                    0.0
+                }
+            })
+            .collect_into_vec(&mut self.time_steps);
+    }
+
+    fn apply_forces(&mut self) {
+        let gravity = self.gravity_axes.apply(self.gravity);
+        let (linear_damping, angular_damping) = (self.linear_damping, self.angular_damping);
+        self.quads
+            .par_iter_mut()
+            .zip(self.time_steps.par_iter())
+            .filter(|(quad, &ts)| quad.body_type == BodyType::Dynamic && ts > 0.0)
+            .for_each(|(quad, &ts)| {
+                quad.velocity += gravity * quad.gravity_scale * ts;
+
+                // Clamped like the drag below, so damping can stop a quad but never reverse it
+                let linear_damping = quad.linear_damping.unwrap_or(linear_damping);
+                quad.velocity *= (1.0 - linear_damping * ts).max(0.0);
+                let angular_damping = quad.angular_damping.unwrap_or(angular_damping);
+                quad.angular_velocity *= (1.0 - angular_damping * ts).max(0.0);
+
+                // Quadratic drag, clamped so a large step can only stop the quad and never reverse it
+                let drag = quad.drag_coefficient * quad.velocity.magnitude() * ts;
+                quad.velocity *= 1.0 - drag.min(1.0);
+            });
+    }
+
+    // Springs share quads, so unlike gravity they are applied one at a time
+    fn apply_springs(&mut self, ts: f32) {
+        for spring in &self.springs {
+            let (Some(a), Some(b)) = (self.quads.get(spring.body_a), self.quads.get(spring.body_b))
+            else {
+                continue;
+            };
+            // Quads that are not being stepped are treated as static until they are
+            let inverse_mass_a = if self.time_steps[spring.body_a] > 0.0 {
+                a.inverse_mass()
+            } else {
+                0.0
+            };
+            let inverse_mass_b = if self.time_steps[spring.body_b] > 0.0 {
+                b.inverse_mass()
+            } else {
+                0.0
+            };
+            let inverse_mass = inverse_mass_a + inverse_mass_b;
+            let difference = b.position - a.position;
+            let length = difference.magnitude();
+            if spring.body_a == spring.body_b || inverse_mass == 0.0 || length == 0.0 {
+                continue;
+            }
+            let direction = difference / length;
+
+            let stiffness = spring
+                .stiffness
+                .min(SpringJoint::max_stiffness(inverse_mass, ts));
+            let damping = spring
+                .damping
+                .min(SpringJoint::max_damping(inverse_mass, ts));
+            let relative_velocity = (b.velocity - a.velocity).dot(direction);
+            // The force on `b`, `a` gets the opposite
+            let force = -stiffness * (length - spring.rest_length) - damping * relative_velocity;
+            let impulse = direction * force * ts;
+
+            self.quads[spring.body_a].velocity -= impulse * inverse_mass_a;
+            self.quads[spring.body_b].velocity += impulse * inverse_mass_b;
+        }
+    }
+
+    // Runs `steps` steps of `ts` seconds. Each quad is solved on its own from the quads of the last iteration and the
+    // results are collected in order, so the result is the same no matter how many threads rayon uses
+    pub fn step_n(&mut self, ts: f32, steps: usize) {
+        for _ in 0..steps {
+            self.step(ts);
+        }
+    }
+
+    // Advances everything by `ts` seconds, call it with the same `ts` every time for a stable simulation. Returns every
+    // pair of quads that touched during the step, sorted by the pair
+    pub fn step(&mut self, ts: f32) -> Vec<ContactEvent> {
+        self.step_count += 1;
+        if self.sleeping_enabled {
+            self.wake_quads(ts);
+        }
+        self.update_time_steps(ts);
+
+        if self.gravity_integration == GravityIntegration::BeforeSolve {
+            self.apply_forces();
+        }
+        self.apply_springs(ts);
+        // Counted for this world only, the collision functions are shared by every world
+        let cap_hits = AtomicUsize::new(0);
+        self.update_one_way_passes(&cap_hits);
+
+        // Impulses only accumulate within a single step, the last step's are kept for warm starting
+        std::mem::swap(&mut self.contact_impulses, &mut self.old_contact_impulses);
+        self.contact_impulses
+            .resize_with(self.quads.len(), Default::default);
+        self.contact_impulses
+            .iter_mut()
+            .for_each(|impulses| impulses.clear());
+        self.collisions
+            .resize_with(self.quads.len(), Default::default);
+        self.collisions
+            .iter_mut()
+            .for_each(|collisions| collisions.clear());
+        self.allowed_penetrations
+            .resize_with(self.quads.len(), Default::default);
+        self.allowed_penetrations
+            .iter_mut()
+            .for_each(|allowed_penetrations| allowed_penetrations.clear());
+        self.contacts
+            .resize_with(self.quads.len(), Default::default);
+        self.contacts
+            .iter_mut()
+            .for_each(|contacts| contacts.clear());
+
+        // Each island keeps iterating until nothing in it moves, islands that are already solved are skipped
+        self.update_islands();
+        let island_solved = self
+            .island_iterations
+            .iter()
+            .map(|_| AtomicBool::new(false))
+            .collect::<Vec<_>>();
+        let mut active_islands = vec![true; island_solved.len()];
+        let mut iterations = 0;
+        while active_islands.contains(&true) && iterations < MAX_PHYSICS_ITERATIONS {
+            for (island, &active) in active_islands.iter().enumerate() {
+                if active {
+                    self.island_iterations[island] += 1;
+                    island_solved[island].store(true, Ordering::Relaxed);
+                }
+            }
+
+            // Both buffers keep their capacity across iterations and steps, so after the
+            // first step `collect_into_vec` writes into the existing allocation
+            std::mem::swap(&mut self.quads, &mut self.old_quads);
+            self.broadphase.build(
+                self.broadphase_cell_size,
+                self.old_quads
+                    .iter()
+                    .zip(&self.time_steps)
+                    .map(|(quad, &ts)| swept_aabb(quad, ts)),
+            );
+            let context = SolveContext {
+                old_quads: &self.old_quads,
+                time_steps: &self.time_steps,
+                islands: &self.islands,
+                active_islands: &active_islands,
+                island_solved: &island_solved,
+                cap_hits: &cap_hits,
+                broadphase: &self.broadphase,
+                one_way_passes: &self.one_way_passes,
+                old_contact_impulses: &self.old_contact_impulses,
+                joints: &self.joints,
+                contact_resolution: self.contact_resolution,
+                sweeping_mode: self.sweeping_mode,
+                sweeping_threshold: self.sweeping_threshold,
+                collision_params: self.collision_params,
+                ignore_internal_edges: self.ignore_internal_edges,
+                jitter_enabled: self.jitter_enabled,
+                jitter_seed: self.jitter_seed,
+                warm_starting: self.warm_starting,
+                relaxation: self.relaxation,
+                record_collisions: self.record_collisions,
+                step_count: self.step_count,
+                iterations,
+            };
+            self.old_quads
+                .par_iter()
+                .zip(self.contact_impulses.par_iter_mut())
+                .zip(self.collisions.par_iter_mut())
+                .zip(self.allowed_penetrations.par_iter_mut())
+                .zip(self.contacts.par_iter_mut())
+                .enumerate()
+                // Each rayon job reuses one list of nearby quads for every quad it solves
+                .map_init(
+                    Vec::new,
+                    |nearby,
+                     (
+                        index,
+                        ((((&quad, impulses), collisions), allowed_penetrations), contacts),
+                    )| {
+                        let quad_contacts = QuadContacts {
+                            impulses,
+                            collisions,
+                            allowed_penetrations,
+                            contacts,
+                        };
+                        context.solve_quad(index, quad, nearby, quad_contacts)
+                    },
+                )
+                .collect_into_vec(&mut self.quads);
+
+            iterations += 1;
+            for (active, solved) in active_islands.iter_mut().zip(&island_solved) {
+                *active = !solved.load(Ordering::Relaxed);
+            }
+        }
+
+        // Pairs that are not in contact anymore are dropped, so their count starts over next time
+        let mut contact_persistence = HashMap::with_capacity(self.contact_persistence.len());
+        for (index, impulses) in self.contact_impulses.iter().enumerate() {
+            for &(other_index, _, _) in impulses {
+                let pair = (index.min(other_index), index.max(other_index));
+                contact_persistence.entry(pair).or_insert_with(|| {
+                    self.contact_persistence.get(&pair).copied().unwrap_or(0) + 1
+                });
+            }
+        }
+        self.contact_persistence = contact_persistence;
+
+        // Each pair is reported once, from the quad with the smaller index unless only the other quad solved it,
+        // like when the smaller one is static
+        let mut contact_events = Vec::new();
+        for (index, contacts) in self.contacts.iter().enumerate() {
+            for (other_index, collision) in contacts {
+                let other_index = *other_index;
+                let other_found_it = self.contacts[other_index]
+                    .iter()
+                    .any(|&(contact_index, _)| contact_index == index);
+                if index > other_index && other_found_it {
+                    continue;
+                }
+                let (a, b, normal) = if index < other_index {
+                    (index, other_index, collision.normal)
+                } else {
+                    (other_index, index, -collision.normal)
+                };
+                contact_events.push(ContactEvent {
+                    a,
+                    b,
+                    normal,
+                    depth: collision.depth,
+                    contact_points: collision.contact_points.clone(),
+                });
+            }
+        }
+        contact_events.sort_unstable_by_key(|event| (event.a, event.b));
+
+        for callback in &mut self.contact_callbacks {
+            for event in &contact_events {
+                callback(event);
+            }
+        }
+
+        self.update_trigger_events(&cap_hits);
+
+        for (&(a, b), &steps) in &self.contact_persistence {
+            if steps == 1 {
+                for index in [a, b] {
+                    let quad = &mut self.quads[index];
+                    if quad.flash_on_collision {
+                        quad.flash_time = FLASH_DURATION;
+                    }
+                }
+            }
+        }
+
+        if self.gravity_integration == GravityIntegration::AfterSolve {
+            self.apply_forces();
+        }
+
+        let mut cap_hits = cap_hits.into_inner();
+        self.solve_capsules(ts, &mut cap_hits);
+
+        // Overlaps are only partly pushed out each step, so a quad is only stuck if it stays overlapped for a while
+        let penetration_slop = self.collision_params.penetration_slop;
+        self.quads.par_iter_mut().for_each(|quad| {
+            if quad.penetration_depth > penetration_slop {
+                quad.stuck_steps += 1;
+            } else {
+                quad.stuck_steps = 0;
+            }
+        });
+
+        self.quads
+            .par_iter_mut()
+            .zip(self.time_steps.par_iter())
+            // Kinematic quads move too, they just dont get pushed around while solving
+            .filter(|(quad, _)| quad.body_type != BodyType::Static)
+            .for_each(|(quad, &ts)| {
+                quad.position += quad.velocity * ts;
+                quad.rotation += quad.angular_velocity * ts;
+            });
+
+        // Sleeping quads are skipped by the time steps, so only awake quads can fall asleep here
+        let (sleeping_enabled, sleep_threshold, sleep_steps) = (
+            self.sleeping_enabled,
+            self.sleep_threshold,
+            self.sleep_steps,
+        );
+        self.quads
+            .par_iter_mut()
+            .zip(self.time_steps.par_iter())
+            .filter(|(quad, &ts)| {
+                quad.body_type == BodyType::Dynamic && (ts > 0.0 || !sleeping_enabled)
+            })
+            .for_each(|(quad, _)| {
+                if !sleeping_enabled || quad.is_moving(sleep_threshold) {
+                    quad.wake();
+                } else {
+                    quad.low_energy_steps += 1;
+                    if quad.low_energy_steps >= sleep_steps {
+                        quad.is_sleeping = true;
+                        quad.velocity = cgmath::vec2(0.0, 0.0);
+                        quad.angular_velocity = 0.0;
+                    }
+                }
+            });
+
+        if self.continuous_velocity_scale {
+            // The scale is per second so it doesnt depend on the fixed update rate
+            let velocity_scale = self.velocity_scale;
+            self.quads
+                .par_iter_mut()
+                .zip(self.time_steps.par_iter())
+                .filter(|(quad, _)| quad.body_type == BodyType::Dynamic)
+                .for_each(|(quad, &ts)| {
+                    let scale = velocity_scale.powf(ts);
+                    quad.velocity *= scale;
+                    quad.angular_velocity *= scale;
+                });
+        }
+
+        self.last_step_report = StepReport {
+            iterations,
+            // Running out of iterations only matters if something still wasnt solved
+            hit_iteration_cap: active_islands.contains(&true),
+            contact_count: self.contact_persistence.len(),
+            collision_iteration_cap_hits: cap_hits,
+        };
+        contact_events
+    }
+}
+
+// Everything solving a quad reads, so the quads can be solved in parallel without sharing the whole world
+struct SolveContext<'a> {
+    old_quads: &'a [Quad],
+    time_steps: &'a [f32],
+    islands: &'a [Option<usize>],
+    active_islands: &'a [bool],
+    island_solved: &'a [AtomicBool],
+    // How many times GJK or EPA gave up this step
+    cap_hits: &'a AtomicUsize,
+    broadphase: &'a Broadphase,
+    one_way_passes: &'a [(usize, usize)],
+    old_contact_impulses: &'a [Vec<ContactImpulses>],
+    joints: &'a [Joint],
+    contact_resolution: ContactResolution,
+    sweeping_mode: SweepingMode,
+    sweeping_threshold: f32,
+    collision_params: CollisionParams,
+    ignore_internal_edges: bool,
+    jitter_enabled: bool,
+    jitter_seed: u64,
+    warm_starting: bool,
+    relaxation: f32,
+    record_collisions: bool,
+    step_count: u64,
+    // How many iterations of this step have already been done
+    iterations: usize,
+}
+
+// What a quad keeps about its contacts between the iterations of a step
+struct QuadContacts<'a> {
+    impulses: &'a mut Vec<ContactImpulses>,
+    collisions: &'a mut Vec<Collision>,
+    allowed_penetrations: &'a mut Vec<(usize, f32)>,
+    contacts: &'a mut Vec<(usize, Collision)>,
+}
+
+impl SolveContext<'_> {
+    // Solves one quad against the quads from the last iteration, returning where it ends up
+    fn solve_quad(
+        &self,
+        index: usize,
+        mut quad: Quad,
+        nearby: &mut Vec<usize>,
+        quad_contacts: QuadContacts<'_>,
+    ) -> Quad {
+        let QuadContacts {
+            impulses,
+            collisions,
+            allowed_penetrations,
+            contacts,
+        } = quad_contacts;
+        let ts = self.time_steps[index];
+        let mut cap_hits = 0;
+        // Quads in solved islands keep what they ended up with in the last iteration
+        let island = self.islands[index];
+        if island.is_none_or(|island| self.active_islands[island]) {
+            quad.penetration_depth = 0.0;
+        }
+        if let Some(island) = island.filter(|&island| self.active_islands[island]) {
+            let solved = &self.island_solved[island];
+            let mut position_delta = cgmath::vec2(0.0, 0.0);
+            let mut velocity_delta = cgmath::vec2(0.0, 0.0);
+            let mut angular_velocity_delta = 0.0;
+
+            self.broadphase.query_into(swept_aabb(&quad, ts), nearby);
+            // Sensors are handled after solving, they never affect `solved`
+            nearby.retain(|&other_index| {
+                let other = &self.old_quads[other_index];
+                let pair = (index.min(other_index), index.max(other_index));
+                quad.collides_with(other)
+                    && !quad.is_sensor
+                    && !other.is_sensor
+                    && self.one_way_passes.binary_search(&pair).is_err()
+            });
+
+            let deepest_contact = match self.contact_resolution {
+                ContactResolution::Simultaneous => None,
+                ContactResolution::DeepestFirst => nearby
+                    .iter()
+                    .filter(|&&other_index| other_index != index)
+                    .filter_map(|&other_index| {
+                        let other = &self.old_quads[other_index];
+                        Some((
+                            other_index,
+                            get_collision(&quad, other, &self.collision_params, &mut cap_hits)?
+                                .depth,
+                        ))
+                    })
+                    .max_by(|(_, a), (_, b)| a.total_cmp(b))
+                    .map(|(other_index, _)| other_index),
+            };
+
+            for &other_index in nearby.iter() {
+                let other = &self.old_quads[other_index];
+                if other_index != index
+                    && deepest_contact.is_none_or(|deepest| deepest == other_index)
+                {
+                    let sweeping_collider = SweepingCollider {
+                        collider: &quad,
+                        // Starts from where the quad has been pushed out to, being pushed out of one
+                        // quad isnt moving fast towards the next one
+                        position_a: quad.position + position_delta,
+                        position_b: (quad.position + position_delta)
+                            + (quad.velocity + velocity_delta) * ts,
+                        rotation_a: quad.rotation,
+                        rotation_b: quad.rotation
+                            + (quad.angular_velocity + angular_velocity_delta) * ts,
+                    };
+
+                    let sweeping_collider_other = SweepingCollider {
+                        collider: other,
+                        position_a: other.position,
+                        position_b: other.position + other.velocity * self.time_steps[other_index],
+                        rotation_a: other.rotation,
+                        rotation_b: other.rotation
+                            + other.angular_velocity * self.time_steps[other_index],
+                    };
+
+                    let sweep = match self.sweeping_mode {
+                        SweepingMode::Disabled | SweepingMode::Speculative => false,
+                        SweepingMode::All => true,
+                        SweepingMode::DynamicVsStatic => other.body_type != BodyType::Dynamic,
+                        SweepingMode::Automatic => {
+                            let moves_far = |sweep: &SweepingCollider<'_, Quad>| {
+                                let size = sweep.collider.scale.x.min(sweep.collider.scale.y);
+                                // How far the corners swing counts too, so fast spinning quads get swept
+                                let swing = (sweep.rotation_b - sweep.rotation_a).abs()
+                                    * sweep.collider.scale.magnitude()
+                                    * 0.5;
+                                sweep.position_a.distance(sweep.position_b) + swing
+                                    > size * self.sweeping_threshold
+                            };
+                            moves_far(&sweeping_collider) || moves_far(&sweeping_collider_other)
+                        }
+                    };
+                    let collision = quad_collision(
+                        &quad,
+                        index,
+                        other,
+                        other_index,
+                        &self.collision_params,
+                        &mut cap_hits,
+                    )
+                    .or_else(|| {
+                        if !sweep {
+                            return None;
+                        }
+                        // Only quads that arent overlapping yet are swept, so a fast quad is stopped
+                        // at the surface before it gets to the other side
+                        get_swept_collision(
+                            &sweeping_collider,
+                            &sweeping_collider_other,
+                            &self.collision_params,
+                            &mut cap_hits,
+                        )
+                    })
+                    .or_else(|| {
+                        if self.sweeping_mode != SweepingMode::Speculative {
+                            return None;
+                        }
+                        // The furthest the quads could get closer by this step,
+                        // ignoring their rotation
+                        let closing_distance =
+                            ((quad.velocity + velocity_delta) - other.velocity).magnitude() * ts;
+                        // Quads that are only just apart, like after being pushed out of each
+                        // other, are left to the normal contacts. Otherwise resting quads fight
+                        // over the tiny gap every iteration and stacks fall apart
+                        get_speculative_collision(
+                            &quad,
+                            other,
+                            closing_distance,
+                            &self.collision_params,
+                            &mut cap_hits,
+                        )
+                        .filter(|collision| -collision.depth > PENETRATION_SLOP)
+                    });
+                    if let Some(mut collision) = collision {
+                        // Swept and speculative contacts dont go through `quad_collision`
+                        collision.orient_towards(other.position - quad.position);
+
+                        // Contacts on the seams between static tiles would make quads catch on them
+                        if self.ignore_internal_edges
+                            && other.body_type == BodyType::Static
+                            && PhysicsWorld::is_internal_edge(
+                                self.old_quads,
+                                other_index,
+                                collision.normal,
+                            )
+                        {
+                            continue;
+                        }
+
+                        // Only part of a deep overlap is pushed out each step, the first time the contact
+                        // is found this step decides how much, and once it is pushed out that far the contact
+                        // is done for this step
+                        let allowed_penetration = if collision.depth > 0.0 {
+                            let params = &self.collision_params;
+                            match allowed_penetrations
+                                .iter()
+                                .find(|&&(contact_index, _)| contact_index == other_index)
+                            {
+                                Some(&(_, allowed_penetration)) => allowed_penetration,
+                                None => {
+                                    let allowed_penetration =
+                                        (collision.depth - params.penetration_slop).max(0.0)
+                                            * (1.0 - params.position_correction_factor);
+                                    allowed_penetrations.push((other_index, allowed_penetration));
+                                    allowed_penetration
+                                }
+                            }
+                        } else {
+                            0.0
+                        };
+                        if allowed_penetration > 0.0
+                            && collision.depth
+                                <= allowed_penetration + self.collision_params.epa_tolerance
+                        {
+                            continue;
+                        }
+
+                        // Perfectly symmetric setups have nothing to tip them over, so nudge the normal
+                        // by a tiny amount that is the same for every run of the same scene
+                        let jitter_angle = if self.jitter_enabled {
+                            jitter(self.jitter_seed, self.step_count, index, other_index)
+                                * JITTER_ANGLE
+                        } else {
+                            0.0
+                        };
+                        collision.rotate_normal(jitter_angle);
+                        if self.record_collisions && self.iterations == 0 {
+                            collisions.push(collision.clone());
+                        }
+
+                        // The other quad resolves the rest of the contact from its side,
+                        // so a heavy quad only takes a small part of the correction
+                        let inverse_mass = quad.inverse_mass();
+                        let other_inverse_mass = other.inverse_mass();
+                        let mass_share = inverse_mass / (inverse_mass + other_inverse_mass);
+                        let inverse_inertia = quad.inverse_inertia();
+                        let other_inverse_inertia = other.inverse_inertia();
+
+                        let tangent = cgmath::vec2(-collision.normal.y, collision.normal.x);
+                        let contact = match impulses
+                            .iter()
+                            .position(|&(contact_index, _, _)| contact_index == other_index)
+                        {
+                            Some(contact) => contact,
+                            None => {
+                                // Contacts that didnt exist last step start from 0
+                                let old_impulses = self
+                                    .old_contact_impulses
+                                    .get(index)
+                                    .filter(|_| self.warm_starting)
+                                    .into_iter()
+                                    .flatten()
+                                    .find(|&&(contact_index, _, _)| contact_index == other_index);
+                                // The contact points arent found in a consistent order, so the total
+                                // from last step is spread evenly over the points there are now
+                                let share =
+                                    WARM_START_FACTOR / collision.contact_points.len() as f32;
+                                let (normal_impulse, tangent_impulse) =
+                                    old_impulses.map_or((0.0, 0.0), |(_, normal, tangent)| {
+                                        (
+                                            normal.iter().sum::<f32>() * share,
+                                            tangent.iter().sum::<f32>() * share,
+                                        )
+                                    });
+                                let mut normal_impulses = [0.0; 2];
+                                let mut tangent_impulses = [0.0; 2];
+                                for (point_index, &contact_point) in
+                                    collision.contact_points.iter().enumerate()
+                                {
+                                    normal_impulses[point_index] = normal_impulse;
+                                    tangent_impulses[point_index] = tangent_impulse;
+                                    let impulse = -normal_impulse * collision.normal
+                                        + tangent_impulse * tangent;
+                                    let offset = contact_point - quad.position;
+                                    velocity_delta += impulse * inverse_mass;
+                                    // Positive rotation is clockwise
+                                    angular_velocity_delta -=
+                                        cross(offset, impulse) * inverse_inertia;
+                                }
+
+                                impulses.push((other_index, normal_impulses, tangent_impulses));
+                                // Replaces the contact from when they were only just touching
+                                match contacts
+                                    .iter_mut()
+                                    .find(|(contact_index, _)| *contact_index == other_index)
+                                {
+                                    Some((_, contact)) => *contact = collision.clone(),
+                                    None => contacts.push((other_index, collision.clone())),
+                                }
+                                impulses.len() - 1
+                            }
+                        };
+                        let (_, accumulated_impulses, accumulated_tangent_impulses) =
+                            &mut impulses[contact];
+
+                        let mut colliding = false;
+                        // The other quad applies the opposite impulses from its side, so they are
+                        // included here too, otherwise this quad would take all of the change in
+                        // velocity no matter how heavy it is
+                        let mut other_velocity_delta = cgmath::vec2(0.0, 0.0);
+                        let mut other_angular_velocity_delta = 0.0;
+                        // The impulses at one contact point change the velocity at the other one,
+                        // so keep going until they agree with each other
+                        for _ in 0..MAX_PHYSICS_ITERATIONS {
+                            let mut impulse_changed = false;
+                            for (point_index, &contact_point) in
+                                collision.contact_points.iter().enumerate()
+                            {
+                                let offset = contact_point - quad.position;
+                                let other_offset = contact_point - other.position;
+                                // How much the relative velocity along `direction` changes from an impulse of 1
+                                let effective_inverse_mass = |direction: cgmath::Vector2<f32>| {
+                                    inverse_mass
+                                        + other_inverse_mass
+                                        + cross(offset, direction).powi(2) * inverse_inertia
+                                        + cross(other_offset, direction).powi(2)
+                                            * other_inverse_inertia
+                                };
+
+                                // Include what the other contact points already applied this iteration,
+                                // otherwise both points of a resting face would each stop the whole quad
+                                let velocity = Quad {
+                                    velocity: quad.velocity + velocity_delta,
+                                    angular_velocity: quad.angular_velocity
+                                        + angular_velocity_delta,
+                                    ..quad
+                                }
+                                .velocity_at(contact_point);
+                                let other_velocity = Quad {
+                                    velocity: other.velocity + other_velocity_delta,
+                                    angular_velocity: other.angular_velocity
+                                        + other_angular_velocity_delta,
+                                    ..*other
+                                }
+                                .velocity_at(contact_point);
+                                let relative_velocity = other_velocity - velocity;
+                                // A speculative contact lets the quads close the gap between them this step
+                                let collision_normal_velocity_length = relative_velocity
+                                    .dot(-collision.normal)
+                                    + collision.depth.min(0.0) / ts;
+                                colliding |= collision_normal_velocity_length >= 0.0;
+
+                                // Stop movement in that direction, clamping the total impulse so the contact
+                                // can only ever push the quads apart, but can take back what it applied before
+                                let accumulated_impulse = &mut accumulated_impulses[point_index];
+                                let old_impulse = *accumulated_impulse;
+                                *accumulated_impulse = (old_impulse
+                                    + collision_normal_velocity_length
+                                        / effective_inverse_mass(collision.normal)
+                                        * self.relaxation)
+                                    .max(0.0);
+                                let impulse =
+                                    -(*accumulated_impulse - old_impulse) * collision.normal;
+
+                                // Friction tries to match the tangential velocity of the other quad, but can
+                                // only push as hard as the normal impulse allows, so a quad at rest stays at rest
+                                let friction = (quad.friction * other.friction).sqrt();
+                                let max_tangent_impulse = friction * *accumulated_impulse;
+                                let tangent_velocity = relative_velocity.dot(tangent);
+                                let accumulated_tangent_impulse =
+                                    &mut accumulated_tangent_impulses[point_index];
+                                let old_tangent_impulse = *accumulated_tangent_impulse;
+                                *accumulated_tangent_impulse = (old_tangent_impulse
+                                    + tangent_velocity / effective_inverse_mass(tangent)
+                                        * self.relaxation)
+                                    .clamp(-max_tangent_impulse, max_tangent_impulse);
+                                let impulse = impulse
+                                    + (*accumulated_tangent_impulse - old_tangent_impulse)
+                                        * tangent;
+
+                                impulse_changed |= impulse.magnitude() > IMPULSE_SLOP;
+                                velocity_delta += impulse * inverse_mass;
+                                other_velocity_delta -= impulse * other_inverse_mass;
+                                // Positive rotation is clockwise
+                                angular_velocity_delta -= cross(offset, impulse) * inverse_inertia;
+                                other_angular_velocity_delta +=
+                                    cross(other_offset, impulse) * other_inverse_inertia;
+                            }
+                            if !impulse_changed {
+                                break;
+                            }
+                        }
+
+                        if colliding {
+                            // A collision has happened, so the physics is not solved
+                            solved.store(false, Ordering::Relaxed);
+
+                            if let Some(mut collision) = quad_collision(
+                                &quad,
+                                index,
+                                other,
+                                other_index,
+                                &self.collision_params,
+                                &mut cap_hits,
+                            ) {
+                                collision.rotate_normal(jitter_angle);
+                                quad.penetration_depth =
+                                    quad.penetration_depth.max(collision.depth);
+
+                                // Move the quad out of collision, as far as it is allowed to this step
+                                position_delta -= collision.normal
+                                    * (collision.depth - allowed_penetration).max(0.0)
+                                    * self.relaxation
+                                    * mass_share;
+                            }
+                        }
+                    } else if self.iterations == 0 {
+                        // Resting contacts are pushed all the way apart, so a quad resting on another can start a
+                        // step just above it. They are still touching, so the contact is reported with the gap as
+                        // a negative depth, but there is nothing to solve
+                        if let Some(touching) = get_speculative_collision(
+                            &quad,
+                            other,
+                            self.collision_params.penetration_slop,
+                            &self.collision_params,
+                            &mut cap_hits,
+                        ) {
+                            contacts.push((other_index, touching));
+                        }
+                    }
+                }
+            }
+
+            for joint in self.joints {
+                let Some((other_index, anchor, other_anchor)) = joint.from_side_of(index) else { continue; };
+                let Some(other) = self.old_quads.get(other_index) else { continue; };
+
+                let inverse_mass = quad.inverse_mass();
+                let other_inverse_mass = other.inverse_mass();
+                let inverse_inertia = quad.inverse_inertia();
+                let other_inverse_inertia = other.inverse_inertia();
+
+                let point = quad.local_to_world(anchor);
+                let other_point = other.local_to_world(other_anchor);
+                let difference = other_point - point;
+                let length = difference.magnitude();
+                // With the anchors on top of each other there is no direction to push them in
+                if length == 0.0 {
+                    continue;
+                }
+                let direction = difference / length;
+                let offset = point - quad.position;
+                let other_offset = other_point - other.position;
+                let effective_inverse_mass = inverse_mass
+                    + other_inverse_mass
+                    + cross(offset, direction).powi(2) * inverse_inertia
+                    + cross(other_offset, direction).powi(2) * other_inverse_inertia;
+
+                // Pull or push the anchors back to the rest length, the other quad does the rest from its side
+                let error = length - joint.rest_length;
+                let mass_share = inverse_mass / (inverse_mass + other_inverse_mass);
+                position_delta += direction * error * self.relaxation * mass_share;
+
+                // Remove the velocity that would change the length
+                let velocity = Quad {
+                    velocity: quad.velocity + velocity_delta,
+                    angular_velocity: quad.angular_velocity + angular_velocity_delta,
+                    ..quad
+                }
+                .velocity_at(point);
+                let relative_velocity = (other.velocity_at(other_point) - velocity).dot(direction);
+                let impulse =
+                    direction * relative_velocity / effective_inverse_mass * self.relaxation;
+                velocity_delta += impulse * inverse_mass;
+                // Positive rotation is clockwise
+                angular_velocity_delta -= cross(offset, impulse) * inverse_inertia;
+
+                if error.abs() > PENETRATION_SLOP || impulse.magnitude() > IMPULSE_SLOP {
+                    solved.store(false, Ordering::Relaxed);
+                }
+            }
+
+            quad.position += position_delta;
+            quad.velocity += velocity_delta;
+            quad.angular_velocity += angular_velocity_delta;
+        }
+        self.cap_hits.fetch_add(cap_hits, Ordering::Relaxed);
+        quad
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Two quads moving into each other with no gravity, `offset` is where `b` starts relative to `a`
+    fn head_on(offset: cgmath::Vector2<f32>, speed: f32) -> PhysicsWorld {
+        let a = Quad {
+            velocity: offset.normalize() * speed,
+            ..Quad::default()
+        };
+        let b = Quad {
+            position: offset,
+            velocity: -offset.normalize() * speed,
+            ..Quad::default()
+        };
+        PhysicsWorld {
+            gravity: cgmath::vec2(0.0, 0.0),
+            ..PhysicsWorld::new(vec![a, b])
+        }
+    }
+
+    fn closing_speed(world: &PhysicsWorld) -> f32 {
+        let [a, b] = [world.quads[0], world.quads[1]];
+        (a.velocity - b.velocity).dot((b.position - a.position).normalize())
+    }
+
+    #[test]
+    fn deep_head_on_overlap_never_speeds_up() {
+        for offset in [
+            cgmath::vec2(0.1, 0.0),
+            cgmath::vec2(0.0, -0.05),
+            cgmath::vec2(0.3, 0.2),
+            cgmath::vec2(-0.001, 0.0),
+        ] {
+            let mut world = head_on(offset, 1.0);
+            let before = closing_speed(&world);
+            let events = world.step(1.0 / 60.0);
+            let after = closing_speed(&world);
+            assert!(
+                after <= before + 0.0001,
+                "closing speed went from {before} to {after} for offset {offset:?}"
+            );
+            // They can still slide past each other, but not into each other at the contact
+            let [a, b] = [world.quads[0], world.quads[1]];
+            let event = &events[0];
+            for &point in &event.contact_points {
+                let normal_speed = (a.velocity_at(point) - b.velocity_at(point)).dot(event.normal);
+                assert!(
+                    normal_speed <= 0.001,
+                    "still closing at {normal_speed} for offset {offset:?}"
+                );
+            }
+        }
+    }
+
+    fn momentum(world: &PhysicsWorld) -> cgmath::Vector2<f32> {
+        world
+            .quads
+            .iter()
+            .map(|quad| quad.velocity * quad.mass)
+            .fold(cgmath::vec2(0.0, 0.0), |a, b| a + b)
+    }
+
+    #[test]
+    fn light_and_heavy_head_on_conserves_momentum() {
+        let light = Quad {
+            position: cgmath::vec2(-2.0, 0.0),
+            velocity: cgmath::vec2(5.0, 0.0),
+            mass: 1.0,
+            ..Quad::default()
+        };
+        let heavy = Quad {
+            position: cgmath::vec2(0.0, 0.0),
+            velocity: cgmath::vec2(-1.0, 0.0),
+            mass: 10.0,
+            ..Quad::default()
+        };
+        let mut world = PhysicsWorld {
+            gravity: cgmath::vec2(0.0, 0.0),
+            ..PhysicsWorld::new(vec![light, heavy])
+        };
+        let before = momentum(&world);
+        for _ in 0..60 {
+            world.step(1.0 / 60.0);
+            let after = momentum(&world);
+            assert!(
+                (after - before).magnitude() < 0.001 * before.magnitude(),
+                "momentum went from {before:?} to {after:?}"
+            );
+        }
+        // They have to have actually hit each other, and the heavy one should barely notice
+        let [light, heavy] = [world.quads[0], world.quads[1]];
+        assert!(light.velocity.x < heavy.velocity.x);
+        assert!((heavy.velocity.x + 1.0).abs() < 1.5);
+    }
+
+    #[test]
+    fn resting_stack_falls_asleep() {
+        let mut quads = vec![Quad {
+            position: cgmath::vec2(0.0, -0.5),
+            scale: cgmath::vec2(20.0, 1.0),
+            body_type: BodyType::Static,
+            ..Quad::default()
+        }];
+        quads.extend((0..4).map(|y| Quad {
+            position: cgmath::vec2(0.0, y as f32 + 0.5),
+            ..Quad::default()
+        }));
+        let mut world = PhysicsWorld::new(quads);
+        world.sleeping_enabled = true;
+        for _ in 0..world.sleep_steps * 3 {
+            world.step(1.0 / 60.0);
+        }
+        for (y, quad) in world.quads[1..].iter().enumerate() {
+            assert!(quad.is_sleeping, "{quad:?} is still awake");
+            assert!((quad.position.y - (y as f32 + 0.5)).abs() < 0.01);
+        }
+    }
+
+    fn capsule_over_floor(capsule: Capsule, floor: Quad) -> PhysicsWorld {
+        let mut world = PhysicsWorld::new(vec![Quad {
+            position: cgmath::vec2(0.0, -0.5),
+            scale: cgmath::vec2(20.0, 1.0),
+            body_type: BodyType::Static,
+            ..floor
+        }]);
+        world.capsules.push(Capsule {
+            position: cgmath::vec2(0.0, 2.0),
+            ..capsule
+        });
+        world
+    }
+
+    #[test]
+    fn capsule_lands_on_floor() {
+        let mut world = capsule_over_floor(Capsule::default(), Quad::default());
+        for _ in 0..120 {
+            world.step(1.0 / 60.0);
+        }
+        let capsule = world.capsules[0];
+        assert!(
+            (capsule.position.y - capsule.radius).abs() < 0.01,
+            "{capsule:?}"
+        );
+        assert!(capsule.velocity.y.abs() < 0.2, "{capsule:?}");
+    }
+
+    #[test]
+    fn capsule_filtered_by_floor_falls_through() {
+        let capsule_on_layer_1 = Capsule {
+            mask: 1,
+            ..Capsule::default()
+        };
+        let floor_on_layer_2 = Quad {
+            layer: 2,
+            mask: 2,
+            ..Quad::default()
+        };
+        let sensor_floor = Quad {
+            is_sensor: true,
+            ..Quad::default()
+        };
+        for (capsule, floor) in [
+            (capsule_on_layer_1, floor_on_layer_2),
+            (Capsule::default(), sensor_floor),
+        ] {
+            let mut world = capsule_over_floor(capsule, floor);
+            for _ in 0..120 {
+                world.step(1.0 / 60.0);
+            }
+            assert!(world.capsules[0].position.y < -2.0);
+        }
+    }
+
+    #[test]
+    fn capsule_jumps_up_through_one_way_floor() {
+        let floor = Quad {
+            one_way: true,
+            ..Quad::default()
+        };
+        let capsule = Capsule {
+            velocity: cgmath::vec2(0.0, 12.0),
+            ..Capsule::default()
+        };
+        let mut world = capsule_over_floor(capsule, floor);
+        world.capsules[0].position.y = -2.0;
+        for _ in 0..180 {
+            world.step(1.0 / 60.0);
+        }
+        let capsule = world.capsules[0];
+        assert!(
+            (capsule.position.y - capsule.radius).abs() < 0.01,
+            "{capsule:?}"
+        );
+    }
+
+    #[test]
+    fn same_result_on_one_thread_and_many() {
+        let pile = || {
+            let mut quads = vec![Quad {
+                position: cgmath::vec2(0.0, -0.5),
+                scale: cgmath::vec2(20.0, 1.0),
+                body_type: BodyType::Static,
+                ..Quad::default()
+            }];
+            quads.extend((0..10).map(|i| Quad {
+                position: cgmath::vec2((i % 5) as f32 * 1.1 - 2.0, (i / 5) as f32 * 1.2 + 1.0),
+                rotation: i as f32 * 0.3,
+                scale: cgmath::vec2(0.5 + (i % 3) as f32 * 0.3, 0.6),
+                ..Quad::default()
+            }));
+            PhysicsWorld::new(quads)
+        };
+        let run = |threads: usize| {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap();
+            pool.install(|| {
+                let mut world = pile();
+                world.step_n(1.0 / 60.0, 500);
+                world.quads
+            })
+        };
+        assert!(run(1) == run(8));
+    }
+
+    // Using the world on its own, without anything from the app
+    #[test]
+    fn standalone_falling_box() {
+        let mut world = PhysicsWorld::default();
+        world.add_quad(Quad {
+            position: cgmath::vec2(0.0, -0.5),
+            scale: cgmath::vec2(10.0, 1.0),
+            body_type: BodyType::Static,
+            ..Quad::default()
+        });
+        let falling = world.add_quad(Quad {
+            position: cgmath::vec2(0.0, 10.0),
+            ..Quad::default()
+        });
+
+        // Still in the air after half a second, falling freely
+        world.step_n(0.01, 50);
+        let quad = world.quads[falling];
+        assert!((quad.velocity.y - world.gravity.y * 0.5).abs() < 0.01);
+        assert!((quad.position.y - (10.0 + world.gravity.y * 0.5 * 0.5 * 0.5)).abs() < 0.1);
+
+        // Then comes to rest on the floor
+        world.step_n(0.01, 300);
+        let quad = world.quads[falling];
+        assert!((quad.position.y - 0.5).abs() < 0.01, "{quad:?}");
+        assert!(quad.velocity.magnitude() < 0.1, "{quad:?}");
+    }
+
+    #[test]
+    fn iteration_cap_is_only_reported_when_unsolved() {
+        // Resting on the floor is solved well before the cap
+        let mut world = PhysicsWorld::default();
+        world.add_quad(Quad {
+            position: cgmath::vec2(0.0, -0.5),
+            scale: cgmath::vec2(10.0, 1.0),
+            body_type: BodyType::Static,
+            ..Quad::default()
+        });
+        world.add_quad(Quad {
+            position: cgmath::vec2(0.0, 0.5),
+            ..Quad::default()
+        });
+        for _ in 0..100 {
+            world.step(0.01);
+            let report = world.last_step_report();
+            assert!(!report.hit_iteration_cap, "{report:?}");
+            assert_eq!(report.collision_iteration_cap_hits, 0);
+        }
+
+        // Pulled towards two points at once, so the joints can never both be satisfied
+        let mut world = PhysicsWorld {
+            gravity: cgmath::vec2(0.0, 0.0),
+            ..PhysicsWorld::new(vec![
+                Quad {
+                    position: cgmath::vec2(-1.0, 0.0),
+                    body_type: BodyType::Static,
+                    ..Quad::default()
+                },
+                Quad::default(),
+                Quad {
+                    position: cgmath::vec2(1.0, 0.0),
+                    body_type: BodyType::Static,
+                    ..Quad::default()
+                },
+            ])
+        };
+        world.joints = [0, 2]
+            .map(|other| Joint {
+                body_a: 1,
+                body_b: other,
+                anchor_a: cgmath::vec2(0.0, 0.0),
+                anchor_b: cgmath::vec2(0.0, 0.0),
+                rest_length: 0.0,
+            })
+            .to_vec();
+        world.step(0.01);
+        let report = world.last_step_report();
+        assert_eq!(report.iterations, MAX_PHYSICS_ITERATIONS);
+        assert!(report.hit_iteration_cap);
+    }
+
+    #[test]
+    fn fast_box_stops_at_a_thin_wall() {
+        for sweeping_mode in SweepingMode::ALL
+            .into_iter()
+            .filter(|&mode| mode != SweepingMode::Disabled)
+        {
+            let mut world = PhysicsWorld {
+                gravity: cgmath::vec2(0.0, 0.0),
+                sweeping_mode,
+                ..PhysicsWorld::new(vec![
+                    Quad {
+                        scale: cgmath::vec2(0.1, 4.0),
+                        body_type: BodyType::Static,
+                        ..Quad::default()
+                    },
+                    Quad {
+                        position: cgmath::vec2(-3.0, 0.3),
+                        velocity: cgmath::vec2(500.0, 0.0),
+                        scale: cgmath::vec2(0.2, 0.2),
+                        ..Quad::default()
+                    },
+                ])
+            };
+            // Moves more than 8 times its width each step, so it would be past the wall without sweeping
+            for step in 0..120 {
+                world.step(1.0 / 60.0);
+                let quad = world.quads[1];
+                // The near face of the wall is at -0.05 and the box is 0.1 from its center to its face
+                assert!(
+                    quad.position.x < -0.14,
+                    "{} went through on step {step}: {quad:?}",
+                    sweeping_mode.name()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn overlapping_boxes_separate_without_speeding_up() {
+        for offset in [cgmath::vec2(0.0, 0.0), cgmath::vec2(0.05, 0.02)] {
+            let mut world = PhysicsWorld {
+                gravity: cgmath::vec2(0.0, 0.0),
+                ..PhysicsWorld::new(vec![
+                    Quad::default(),
+                    Quad {
+                        position: offset,
+                        ..Quad::default()
+                    },
+                ])
+            };
+            let params = world.collision_params;
+            let mut separated_at = None;
+            for step in 0..60 {
+                world.step(1.0 / 60.0);
+                let [a, b] = [world.quads[0], world.quads[1]];
+                // Pushed apart directly, so none of it turns into velocity
+                for quad in [a, b] {
+                    assert!(
+                        quad.velocity.magnitude() < 0.001 && quad.angular_velocity.abs() < 0.001,
+                        "step {step} offset {offset:?}: {quad:?}"
+                    );
+                }
+                let depth = get_collision(&a, &b, &params, &mut 0).map_or(0.0, |c| c.depth);
+                if step == 0 {
+                    // Only part of the overlap is pushed out in the first step
+                    assert!(depth > 0.5, "popped apart to a depth of {depth}");
+                }
+                if depth <= params.penetration_slop + params.epa_tolerance {
+                    separated_at.get_or_insert(step);
+                }
+            }
+            let separated_at = separated_at.expect("never separated");
+            assert!(
+                separated_at > 5,
+                "separated after only {separated_at} steps"
+            );
+        }
+    }
+
+    #[test]
+    fn landed_box_has_one_contact_every_step() {
+        let mut world = PhysicsWorld::default();
+        let floor = world.add_quad(Quad {
+            position: cgmath::vec2(0.0, -0.5),
+            scale: cgmath::vec2(10.0, 1.0),
+            body_type: BodyType::Static,
+            ..Quad::default()
+        });
+        let falling = world.add_quad(Quad {
+            position: cgmath::vec2(0.0, 3.0),
+            ..Quad::default()
+        });
+        // Callbacks dont have to be `Send`, so they can share state with `Rc`
+        let called = std::rc::Rc::new(std::cell::Cell::new(0));
+        world.on_contact(Box::new({
+            let called = called.clone();
+            move |_| called.set(called.get() + 1)
+        }));
+
+        let mut landed = false;
+        for step in 0..300 {
+            let events = world.step(1.0 / 60.0);
+            assert_eq!(called.replace(0), events.len());
+            landed |= !events.is_empty();
+            if landed {
+                assert_eq!(events.len(), 1, "step {step}: {events:?}");
+                let event = &events[0];
+                assert_eq!((event.a, event.b), (floor, falling));
+                assert!((event.normal - cgmath::vec2(0.0, 1.0)).magnitude() < 0.01);
+            }
+        }
+        assert!(landed);
+    }
+}